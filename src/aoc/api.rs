@@ -0,0 +1,200 @@
+use std::{env, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::{redirect::Policy, Client};
+
+use super::{
+    error::AocError,
+    leaderboard::{fetch_leaderboard, Leaderboard, LeaderboardAuth},
+    puzzle::{fetch_puzzle_details, PuzzleDetails},
+};
+
+/// Base URL used when `AOC_BASE_URL` isn't set
+pub const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+
+/// User-Agent used when `AOC_USER_AGENT` isn't set. AoC asks automated tools to say who they are
+/// and where to find out more.
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "advent-of-code-friend/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/giraugh/advent-of-code-friend)"
+);
+
+/// How long a request to AoC can take before it's given up on, so a hung connection can't hold
+/// up everyone waiting on the same fetch
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A source of Advent of Code data
+#[async_trait]
+pub trait AocApi: Send + Sync {
+    /// Fetch the JSON for a private leaderboard
+    async fn fetch_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        auth: &LeaderboardAuth,
+    ) -> Result<Leaderboard, AocError>;
+
+    /// Fetch the details of a single puzzle
+    async fn fetch_puzzle_details(
+        &self,
+        year: usize,
+        day: usize,
+    ) -> Result<PuzzleDetails, AocError>;
+}
+
+/// Fetches data over HTTP from the AoC website (or anything pretending to be it)
+pub struct HttpAocApi {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpAocApi {
+    pub fn new(base_url: &str, user_agent: &str) -> Self {
+        Self {
+            // Redirects are how AoC signals an expired session, so we want to see them
+            client: Client::builder()
+                .redirect(Policy::none())
+                .user_agent(user_agent)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("to build http client"),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Uses `AOC_BASE_URL` and `AOC_USER_AGENT` from the environment, falling back to the real
+    /// AoC website and a User-Agent that points to this repository
+    pub fn from_env() -> Self {
+        let base_url = env::var("AOC_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_owned());
+        let user_agent =
+            env::var("AOC_USER_AGENT").unwrap_or_else(|_| DEFAULT_USER_AGENT.to_owned());
+        log::info!("Using {base_url} for Advent of Code requests, as {user_agent}");
+        Self::new(&base_url, &user_agent)
+    }
+}
+
+#[async_trait]
+impl AocApi for HttpAocApi {
+    async fn fetch_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        auth: &LeaderboardAuth,
+    ) -> Result<Leaderboard, AocError> {
+        fetch_leaderboard(&self.client, &self.base_url, event_id, leaderboard_id, auth).await
+    }
+
+    async fn fetch_puzzle_details(
+        &self,
+        year: usize,
+        day: usize,
+    ) -> Result<PuzzleDetails, AocError> {
+        fetch_puzzle_details(&self.client, &self.base_url, year, day).await
+    }
+}
+
+#[cfg(test)]
+pub use fixture::FixtureAocApi;
+
+#[cfg(test)]
+mod fixture {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+
+    use super::AocApi;
+    use crate::aoc::{
+        AocError, Leaderboard, LeaderboardAuth, LeaderboardCacheKey, PuzzleDetails, PuzzleKey,
+    };
+
+    /// Serves leaderboards and puzzles from memory, for use in tests
+    #[derive(Default)]
+    pub struct FixtureAocApi {
+        leaderboards: HashMap<LeaderboardCacheKey, String>,
+        puzzles: HashMap<PuzzleKey, PuzzleDetails>,
+        rejected: Vec<LeaderboardAuth>,
+        requests: AtomicUsize,
+        delay: Duration,
+    }
+
+    impl FixtureAocApi {
+        /// Serve this leaderboard JSON for the given event and leaderboard
+        pub fn with_leaderboard(
+            mut self,
+            event_id: &str,
+            leaderboard_id: &str,
+            json: &str,
+        ) -> Self {
+            self.leaderboards.insert(
+                LeaderboardCacheKey::new(event_id, leaderboard_id),
+                json.to_owned(),
+            );
+            self
+        }
+
+        /// Serve a puzzle with this name for the given year and day
+        pub fn with_puzzle(mut self, year: usize, day: usize, name: &str) -> Self {
+            self.puzzles.insert(
+                (year, day),
+                PuzzleDetails {
+                    name: name.to_owned(),
+                },
+            );
+            self
+        }
+
+        /// Answer every leaderboard request made with these credentials as if they've expired
+        pub fn with_rejected_auth(mut self, auth: LeaderboardAuth) -> Self {
+            self.rejected.push(auth);
+            self
+        }
+
+        /// Take this long to answer each request, so tests can make requests overlap
+        pub fn with_delay(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        /// How many requests have been made to this api
+        pub fn request_count(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl AocApi for FixtureAocApi {
+        async fn fetch_leaderboard(
+            &self,
+            event_id: &str,
+            leaderboard_id: &str,
+            auth: &LeaderboardAuth,
+        ) -> Result<Leaderboard, AocError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if self.rejected.contains(auth) {
+                return Err(AocError::SessionExpired);
+            }
+            let json = self
+                .leaderboards
+                .get(&LeaderboardCacheKey::new(event_id, leaderboard_id))
+                .ok_or(AocError::NotFound)?;
+            Ok(serde_json::from_str(json)?)
+        }
+
+        async fn fetch_puzzle_details(
+            &self,
+            year: usize,
+            day: usize,
+        ) -> Result<PuzzleDetails, AocError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            let puzzle = self.puzzles.get(&(year, day)).ok_or(AocError::NotFound)?;
+            Ok(puzzle.clone())
+        }
+    }
+}
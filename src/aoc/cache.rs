@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+
+use super::AocError;
+
+type SharedFetch<V> = Shared<BoxFuture<'static, Result<V, AocError>>>;
+
+/// Values fetched from AoC, shared by everything that asks for them.
+///
+/// Lookups for different keys never wait for each other, and anyone asking for a key that's
+/// already being fetched the same way (`F`, like which credentials are used) waits for that
+/// fetch instead of starting another one.
+pub struct FetchCache<K, V, F = ()> {
+    slots: Arc<Mutex<HashMap<K, Slot<V, F>>>>,
+}
+
+struct Slot<V, F> {
+    value: Option<V>,
+    fetches: HashMap<F, SharedFetch<V>>,
+}
+
+impl<V, F> Default for Slot<V, F> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            fetches: HashMap::new(),
+        }
+    }
+}
+
+impl<K, V, F> FetchCache<K, V, F>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    F: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The last value fetched for a key, however old it is
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.slots().get(key).and_then(|slot| slot.value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.slots().entry(key).or_default().value = Some(value);
+    }
+
+    /// Fetch a key, or wait for the fetch that's already running for it the same way. A
+    /// successful fetch replaces the cached value, and a failed one leaves it as it was.
+    pub async fn fetch(
+        &self,
+        key: K,
+        fetcher: F,
+        fetch: impl FnOnce() -> BoxFuture<'static, Result<V, AocError>>,
+    ) -> Result<V, AocError> {
+        self.start_fetch(key, fetcher, fetch).await
+    }
+
+    /// Start fetching a key in the background, unless it's already being fetched the same way
+    pub fn refresh(
+        &self,
+        key: K,
+        fetcher: F,
+        fetch: impl FnOnce() -> BoxFuture<'static, Result<V, AocError>>,
+    ) {
+        tokio::spawn(self.start_fetch(key, fetcher, fetch));
+    }
+
+    fn start_fetch(
+        &self,
+        key: K,
+        fetcher: F,
+        fetch: impl FnOnce() -> BoxFuture<'static, Result<V, AocError>>,
+    ) -> SharedFetch<V> {
+        let mut slots = self.slots();
+        let slot = slots.entry(key.clone()).or_default();
+        if let Some(in_flight) = slot.fetches.get(&fetcher) {
+            return in_flight.clone();
+        }
+
+        // Whoever polls the fetch first saves its result for everyone
+        let fetch = fetch();
+        let finished_slots = self.slots.clone();
+        let finished_fetcher = fetcher.clone();
+        let shared = async move {
+            let result = fetch.await;
+            let mut slots = lock(&finished_slots);
+            let slot = slots.entry(key).or_default();
+            slot.fetches.remove(&finished_fetcher);
+            if let Ok(value) = &result {
+                slot.value = Some(value.clone());
+            }
+            result
+        }
+        .boxed()
+        .shared();
+        slot.fetches.insert(fetcher, shared.clone());
+        shared
+    }
+
+    fn slots(&self) -> MutexGuard<'_, HashMap<K, Slot<V, F>>> {
+        lock(&self.slots)
+    }
+}
+
+/// Lock the slots. They're only held for a moment and never across an await, so a panic can't
+/// leave them half updated.
+fn lock<K, V, F>(slots: &Mutex<HashMap<K, Slot<V, F>>>) -> MutexGuard<'_, HashMap<K, Slot<V, F>>> {
+    slots
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
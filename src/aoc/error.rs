@@ -0,0 +1,138 @@
+use std::{error::Error, fmt, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use reqwest::{Response, StatusCode};
+
+/// Everything that can go wrong while talking to Advent of Code.
+/// Errors can be cloned, so one failed fetch can be reported to everyone waiting for it.
+#[derive(Debug, Clone)]
+pub enum AocError {
+    /// The session token was rejected (AoC redirects to the login page or serves html)
+    SessionExpired,
+
+    /// No such leaderboard or puzzle exists
+    NotFound,
+
+    /// The session token's user isn't a member of the leaderboard
+    Forbidden,
+
+    /// The view key doesn't belong to the leaderboard, or has been reset by its owner
+    ViewKeyRejected,
+
+    /// AoC is asking us to slow down
+    RateLimited,
+
+    /// The leaderboard was fetched too recently to fetch again until this time
+    TooSoon(DateTime<Utc>),
+
+    /// The request couldn't be completed
+    Network(Arc<reqwest::Error>),
+
+    /// AoC responded, but not with what we expected
+    Schema(String),
+}
+
+impl AocError {
+    /// What the user can do to fix this error
+    pub fn hint(&self) -> &'static str {
+        match self {
+            AocError::SessionExpired => "Log in to Advent of Code again, then rerun `/register` with the new `session` cookie.",
+            AocError::NotFound => "Check that the leaderboard ID is correct (it's the number at the end of the leaderboard URL) and rerun `/register`.",
+            AocError::Forbidden => "The account the session token belongs to must be a member of the leaderboard. Join it, or rerun `/register` with a token from a member's account.",
+            AocError::ViewKeyRejected => "Ask the owner of the leaderboard for its current view key (shown on the leaderboard page) and rerun `/register`.",
+            AocError::RateLimited => "Advent of Code is getting too many requests, please try again in a few minutes.",
+            AocError::TooSoon(_) => "Advent of Code asks that each leaderboard is fetched at most once every 15 minutes.",
+            AocError::Network(_) => "Advent of Code may be down or overloaded, please try again in a few minutes.",
+            AocError::Schema(_) => "Advent of Code may have changed its API. If this keeps happening, please open an issue on the GitHub repository.",
+        }
+    }
+
+    /// Whether trying again later might work, without anyone changing anything
+    pub fn is_temporary(&self) -> bool {
+        matches!(self, AocError::RateLimited | AocError::Network(_))
+    }
+
+    /// Whether the credentials were rejected, so others might still work
+    pub fn is_auth(&self) -> bool {
+        matches!(
+            self,
+            AocError::SessionExpired | AocError::Forbidden | AocError::ViewKeyRejected
+        )
+    }
+
+    /// A full explanation suitable to show in discord
+    pub fn user_message(&self) -> String {
+        let too_soon;
+        let description = match self {
+            AocError::SessionExpired => "Your session token has expired or is invalid.",
+            AocError::NotFound => "That leaderboard doesn't exist.",
+            AocError::Forbidden => "The session token doesn't have access to that leaderboard.",
+            AocError::ViewKeyRejected => "The view key doesn't give access to that leaderboard.",
+            AocError::RateLimited => "Advent of Code has rate limited this bot.",
+            AocError::TooSoon(next) => {
+                too_soon = format!(
+                    "That leaderboard was fetched recently, it can be fetched again <t:{}:R>.",
+                    next.timestamp()
+                );
+                &too_soon
+            }
+            AocError::Network(_) => "Couldn't reach Advent of Code.",
+            AocError::Schema(_) => "Advent of Code sent a response that couldn't be understood.",
+        };
+        format!("{description}\n\n{}", self.hint())
+    }
+}
+
+impl fmt::Display for AocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AocError::SessionExpired => write!(f, "session token expired or invalid"),
+            AocError::NotFound => write!(f, "not found"),
+            AocError::Forbidden => write!(f, "not a member of this leaderboard"),
+            AocError::ViewKeyRejected => write!(f, "view key rejected"),
+            AocError::RateLimited => write!(f, "rate limited"),
+            AocError::TooSoon(next) => write!(f, "fetched too recently, next allowed at {next}"),
+            AocError::Network(err) => write!(f, "network error: {err}"),
+            AocError::Schema(reason) => write!(f, "unexpected response: {reason}"),
+        }
+    }
+}
+
+impl Error for AocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AocError::Network(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AocError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_decode() {
+            AocError::Schema(err.to_string())
+        } else {
+            // The URL can include a view key, which shouldn't end up in logs
+            AocError::Network(Arc::new(err.without_url()))
+        }
+    }
+}
+
+impl From<serde_json::Error> for AocError {
+    fn from(err: serde_json::Error) -> Self {
+        AocError::Schema(err.to_string())
+    }
+}
+
+/// Turn an unsuccessful response from AoC into the matching error
+pub fn check_status(res: Response) -> Result<Response, AocError> {
+    match res.status() {
+        // AoC redirects to the login page when the session isn't valid
+        status if status.is_redirection() => Err(AocError::SessionExpired),
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(AocError::SessionExpired),
+        StatusCode::FORBIDDEN => Err(AocError::Forbidden),
+        StatusCode::NOT_FOUND => Err(AocError::NotFound),
+        StatusCode::TOO_MANY_REQUESTS => Err(AocError::RateLimited),
+        _ => Ok(res.error_for_status()?),
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::{sync::Mutex, time::Instant};
+
+use super::{
+    api::{AocApi, HttpAocApi},
+    error::AocError,
+    leaderboard::{AuthFingerprint, Leaderboard, LeaderboardAuth, LeaderboardCacheKey},
+    puzzle::PuzzleDetails,
+};
+
+/// AoC asks that private leaderboards aren't fetched more than once every 15 minutes
+pub const MIN_LEADERBOARD_INTERVAL_SECS: u64 = 15 * 60;
+
+/// Requests per minute allowed when `AOC_MAX_REQUESTS_PER_MINUTE` isn't set
+const DEFAULT_MAX_REQUESTS_PER_MINUTE: usize = 20;
+
+/// Keeps every request to AoC within its limits, however many guilds are asking. Each
+/// leaderboard is fetched at most once per interval with the same credentials, even if several
+/// guilds have registered it, and requests wait their turn once the per-minute cap is reached.
+///
+/// New credentials can be checked within the interval, so a guild can replace an expired token
+/// or register a board another guild already uses without waiting.
+pub struct RequestGovernor<A = HttpAocApi> {
+    api: A,
+    min_leaderboard_interval: Duration,
+    max_requests_per_minute: usize,
+    /// When each leaderboard was fetched with each set of credentials within the interval
+    leaderboards: Mutex<HashMap<LeaderboardCacheKey, Vec<(AuthFingerprint, Instant)>>>,
+    /// When each request in the last minute was sent, oldest first
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+impl RequestGovernor {
+    /// Uses `AOC_MAX_REQUESTS_PER_MINUTE` from the environment for the per-minute cap
+    pub fn from_env(api: HttpAocApi) -> Self {
+        let max_requests_per_minute = env::var("AOC_MAX_REQUESTS_PER_MINUTE")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .unwrap_or(DEFAULT_MAX_REQUESTS_PER_MINUTE);
+        log::info!("Sending at most {max_requests_per_minute} requests a minute to Advent of Code");
+        Self::new(api, max_requests_per_minute)
+    }
+}
+
+impl<A: AocApi> RequestGovernor<A> {
+    pub fn new(api: A, max_requests_per_minute: usize) -> Self {
+        Self {
+            api,
+            min_leaderboard_interval: Duration::from_secs(MIN_LEADERBOARD_INTERVAL_SECS),
+            max_requests_per_minute: max_requests_per_minute.max(1),
+            leaderboards: Mutex::new(HashMap::new()),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn api(&self) -> &A {
+        &self.api
+    }
+
+    /// Wait until a request can be sent without going over the per-minute cap. Waiting requests
+    /// hold the lock, so they're sent in the order they arrived.
+    async fn wait_for_turn(&self) {
+        let mut recent = self.recent.lock().await;
+        loop {
+            let now = Instant::now();
+            while recent
+                .front()
+                .is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(60))
+            {
+                recent.pop_front();
+            }
+            match recent.front() {
+                Some(oldest) if recent.len() >= self.max_requests_per_minute => {
+                    let wait_until = *oldest + Duration::from_secs(60);
+                    log::warn!(
+                        "Reached {} Advent of Code requests a minute, waiting {}s",
+                        self.max_requests_per_minute,
+                        wait_until.duration_since(now).as_secs()
+                    );
+                    tokio::time::sleep_until(wait_until).await;
+                }
+                _ => break,
+            }
+        }
+        recent.push_back(Instant::now());
+    }
+}
+
+#[async_trait]
+impl<A: AocApi> AocApi for RequestGovernor<A> {
+    async fn fetch_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        auth: &LeaderboardAuth,
+    ) -> Result<Leaderboard, AocError> {
+        // Refuse if this leaderboard was fetched too recently with these credentials, otherwise
+        // claim this interval for them
+        let key = LeaderboardCacheKey::new(event_id, leaderboard_id);
+        let fingerprint = auth.fingerprint();
+        {
+            let mut leaderboards = self.leaderboards.lock().await;
+            let now = Instant::now();
+            let fetches = leaderboards.entry(key.clone()).or_default();
+            fetches.retain(|(_, last)| *last + self.min_leaderboard_interval > now);
+            if let Some((_, last)) = fetches
+                .iter()
+                .find(|(fetched_with, _)| *fetched_with == fingerprint)
+            {
+                log::info!("Not fetching leaderboard {leaderboard_id} for {event_id}, it was fetched too recently");
+                let wait = chrono::Duration::from_std(*last + self.min_leaderboard_interval - now)
+                    .unwrap_or_else(|_| chrono::Duration::zero());
+                return Err(AocError::TooSoon(Utc::now() + wait));
+            }
+            fetches.push((fingerprint, now));
+        }
+
+        // Send the request
+        self.wait_for_turn().await;
+        log::info!("Fetching leaderboard {leaderboard_id} for {event_id} from Advent of Code");
+        let start = Instant::now();
+        let result = self
+            .api
+            .fetch_leaderboard(event_id, leaderboard_id, auth)
+            .await;
+        log_result("leaderboard", start, &result);
+
+        // Anything that reached AoC counts, but it's fine to try again after a network failure,
+        // or once rejected credentials have been replaced
+        if let Err(error) = &result {
+            if matches!(error, AocError::Network(_)) || error.is_auth() {
+                if let Some(fetches) = self.leaderboards.lock().await.get_mut(&key) {
+                    fetches.retain(|(fetched_with, _)| *fetched_with != fingerprint);
+                }
+            }
+        }
+        result
+    }
+
+    async fn fetch_puzzle_details(
+        &self,
+        year: usize,
+        day: usize,
+    ) -> Result<PuzzleDetails, AocError> {
+        self.wait_for_turn().await;
+        log::info!("Fetching puzzle for day {day} of {year} from Advent of Code");
+        let start = Instant::now();
+        let result = self.api.fetch_puzzle_details(year, day).await;
+        log_result("puzzle", start, &result);
+        result
+    }
+}
+
+fn log_result<T>(what: &str, start: Instant, result: &Result<T, AocError>) {
+    let elapsed = start.elapsed().as_millis();
+    match result {
+        Ok(_) => log::info!("Fetched {what} in {elapsed}ms"),
+        Err(err) => log::warn!("Failed to fetch {what} after {elapsed}ms: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::RequestGovernor;
+    use crate::aoc::{AocApi, AocError, FixtureAocApi, LeaderboardAuth};
+
+    const LEADERBOARD_JSON: &str = include_str!("../../fixtures/leaderboard_2023.json");
+
+    #[tokio::test(start_paused = true)]
+    async fn test_leaderboard_interval() {
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+            .with_leaderboard("2023", "2", LEADERBOARD_JSON);
+        let governor = RequestGovernor::new(api, 10);
+        let auth = LeaderboardAuth::ViewKey("key".to_owned());
+
+        governor
+            .fetch_leaderboard("2023", "1", &auth)
+            .await
+            .unwrap();
+
+        // The same board can't be fetched again straight away with the same credentials
+        assert!(matches!(
+            governor.fetch_leaderboard("2023", "1", &auth).await,
+            Err(AocError::TooSoon(_))
+        ));
+
+        // But other credentials can be checked, once
+        let other_auth = LeaderboardAuth::SessionToken("token".to_owned());
+        governor
+            .fetch_leaderboard("2023", "1", &other_auth)
+            .await
+            .unwrap();
+        assert!(matches!(
+            governor.fetch_leaderboard("2023", "1", &other_auth).await,
+            Err(AocError::TooSoon(_))
+        ));
+
+        // And other boards can be fetched
+        governor
+            .fetch_leaderboard("2023", "2", &auth)
+            .await
+            .unwrap();
+
+        // And it can be fetched again once the interval has passed
+        tokio::time::advance(Duration::from_secs(15 * 60)).await;
+        governor
+            .fetch_leaderboard("2023", "1", &auth)
+            .await
+            .unwrap();
+        assert_eq!(governor.api().request_count(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rejected_credentials_can_retry() {
+        let expired = LeaderboardAuth::SessionToken("expired".to_owned());
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+            .with_rejected_auth(expired.clone());
+        let governor = RequestGovernor::new(api, 10);
+
+        // Rejected credentials don't use up the interval, so they can be tried again once fixed
+        for _ in 0..2 {
+            assert!(matches!(
+                governor.fetch_leaderboard("2023", "1", &expired).await,
+                Err(AocError::SessionExpired)
+            ));
+        }
+        assert_eq!(governor.api().request_count(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_per_minute_cap() {
+        let api = FixtureAocApi::default()
+            .with_puzzle(2023, 1, "Trebuchet?!")
+            .with_puzzle(2023, 2, "Cube Conundrum")
+            .with_puzzle(2023, 3, "Gear Ratios");
+        let governor = RequestGovernor::new(api, 2);
+
+        // The first two requests are sent straight away, and the third waits for a minute
+        let start = Instant::now();
+        governor.fetch_puzzle_details(2023, 1).await.unwrap();
+        governor.fetch_puzzle_details(2023, 2).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        governor.fetch_puzzle_details(2023, 3).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(60));
+        assert_eq!(governor.api().request_count(), 3);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, write},
+    io,
+    ops::RangeBounds,
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use super::{LeaderboardCacheEntry, COMBINED_ID_SEPARATOR};
+use crate::scoring::merge_leaderboards;
+
+/// Directory used when `SNAPSHOT_DIR` isn't set
+const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+/// How many days of snapshots are kept when `SNAPSHOT_RETENTION_DAYS` isn't set
+const DEFAULT_RETENTION_DAYS: i64 = 365;
+
+/// Stores every fetched leaderboard on disk, laid out as `{dir}/{event}/{leaderboard_id}/{unix_ts}.json`
+#[derive(Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+    /// How long snapshots are kept for, forever if not set
+    retention: Option<Duration>,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            retention: None,
+        }
+    }
+
+    /// Delete snapshots once they're older than this. The latest snapshot of each leaderboard
+    /// is always kept.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Uses `SNAPSHOT_DIR` and `SNAPSHOT_RETENTION_DAYS` from the environment, falling back to
+    /// `./snapshots` and a year
+    pub fn from_env() -> Self {
+        let retention_days = env::var("SNAPSHOT_RETENTION_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+        Self::new(env::var("SNAPSHOT_DIR").unwrap_or_else(|_| DEFAULT_SNAPSHOT_DIR.to_owned()))
+            .with_retention(Duration::days(retention_days))
+    }
+
+    fn leaderboard_dir(&self, event_id: &str, leaderboard_id: &str) -> PathBuf {
+        self.dir.join(event_id).join(leaderboard_id)
+    }
+
+    /// Save a snapshot of a leaderboard, deleting any of its snapshots that are past retention
+    pub fn record(&self, entry: &LeaderboardCacheEntry) -> io::Result<()> {
+        let (event_id, leaderboard_id) = (&entry.leaderboard.event, &entry.leaderboard_id);
+        let dir = self.leaderboard_dir(event_id, leaderboard_id);
+        create_dir_all(&dir)?;
+        write(
+            dir.join(format!("{}.json", entry.created_at.timestamp())),
+            serde_json::to_string(&entry.leaderboard)?,
+        )?;
+
+        if let Some(retention) = self.retention {
+            self.prune(event_id, leaderboard_id, entry.created_at - retention)?;
+        }
+        Ok(())
+    }
+
+    /// Delete every snapshot of a leaderboard taken before a time, except the latest
+    fn prune(&self, event_id: &str, leaderboard_id: &str, before: DateTime<Utc>) -> io::Result<()> {
+        let times = self.snapshot_times(event_id, leaderboard_id)?;
+        let latest = times.iter().max().copied();
+        for time in times {
+            if time < before && Some(time) != latest {
+                remove_file(
+                    self.leaderboard_dir(event_id, leaderboard_id)
+                        .join(format!("{}.json", time.timestamp())),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get every snapshot of a leaderboard taken within a time range, oldest first
+    pub fn query(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> io::Result<Vec<LeaderboardCacheEntry>> {
+        // Find the times of matching snapshots from their file names
+        let mut times: Vec<_> = self
+            .snapshot_times(event_id, leaderboard_id)?
+            .into_iter()
+            .filter(|time| range.contains(time))
+            .collect();
+        times.sort();
+
+        // Then read them
+        times
+            .into_iter()
+            .map(|time| self.read_snapshot(event_id, leaderboard_id, time))
+            .collect()
+    }
+
+    /// Get every snapshot of several leaderboards combined into one taken within a time range,
+    /// oldest first. Each time one of them was saved, it's merged with the latest snapshots of
+    /// the others, once they all have one in the range.
+    pub fn query_combined(
+        &self,
+        event_id: &str,
+        leaderboard_ids: &[&str],
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> io::Result<Vec<LeaderboardCacheEntry>> {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        if let [leaderboard_id] = leaderboard_ids {
+            return self.query(event_id, leaderboard_id, range);
+        }
+
+        // Every snapshot of every leaderboard, oldest first
+        let mut snapshots = Vec::new();
+        for leaderboard_id in leaderboard_ids {
+            snapshots.extend(self.query(event_id, leaderboard_id, range)?);
+        }
+        snapshots.sort_by_key(|snapshot| snapshot.created_at);
+
+        // Then merge them as they come in
+        let combined_id = leaderboard_ids.join(&COMBINED_ID_SEPARATOR.to_string());
+        let mut latest: HashMap<String, LeaderboardCacheEntry> = HashMap::new();
+        let mut combined = Vec::new();
+        for snapshot in snapshots {
+            let created_at = snapshot.created_at;
+            latest.insert(snapshot.leaderboard_id.clone(), snapshot);
+            let leaderboards: Vec<_> = leaderboard_ids
+                .iter()
+                .filter_map(|leaderboard_id| latest.get(*leaderboard_id))
+                .map(|snapshot| &snapshot.leaderboard)
+                .collect();
+            if leaderboards.len() == leaderboard_ids.len() {
+                combined.push(LeaderboardCacheEntry {
+                    leaderboard: merge_leaderboards(&leaderboards),
+                    leaderboard_id: combined_id.clone(),
+                    created_at,
+                });
+            }
+        }
+        Ok(combined)
+    }
+
+    /// Get the most recent snapshot of a leaderboard
+    pub fn latest(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+    ) -> io::Result<Option<LeaderboardCacheEntry>> {
+        self.snapshot_times(event_id, leaderboard_id)?
+            .into_iter()
+            .max()
+            .map(|time| self.read_snapshot(event_id, leaderboard_id, time))
+            .transpose()
+    }
+
+    /// Every (event, leaderboard id) that has at least one snapshot
+    pub fn leaderboards(&self) -> io::Result<Vec<(String, String)>> {
+        let mut keys = Vec::new();
+        for event in subdirectories(&self.dir)? {
+            for leaderboard in subdirectories(&self.dir.join(&event))? {
+                keys.push((event.clone(), leaderboard));
+            }
+        }
+        Ok(keys)
+    }
+
+    fn snapshot_times(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+    ) -> io::Result<Vec<DateTime<Utc>>> {
+        let dir = self.leaderboard_dir(event_id, leaderboard_id);
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        // Ignore anything that isn't named like a snapshot
+        let mut times = Vec::new();
+        for entry in entries {
+            let file_name = entry?.file_name();
+            let time = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|ts| ts.parse().ok())
+                .and_then(|ts| Utc.timestamp_opt(ts, 0).single());
+            times.extend(time);
+        }
+        Ok(times)
+    }
+
+    fn read_snapshot(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        time: DateTime<Utc>,
+    ) -> io::Result<LeaderboardCacheEntry> {
+        let path = self
+            .leaderboard_dir(event_id, leaderboard_id)
+            .join(format!("{}.json", time.timestamp()));
+        Ok(LeaderboardCacheEntry {
+            leaderboard: serde_json::from_str(&read_to_string(path)?)?,
+            leaderboard_id: leaderboard_id.to_owned(),
+            created_at: time,
+        })
+    }
+}
+
+fn subdirectories(dir: &PathBuf) -> io::Result<Vec<String>> {
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            names.extend(entry.file_name().into_string().ok());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env::temp_dir, fs::remove_dir_all};
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::SnapshotStore;
+    use crate::aoc::LeaderboardCacheEntry;
+
+    const LEADERBOARD_JSON: &str = include_str!("../../fixtures/leaderboard_2023.json");
+    const LATER_LEADERBOARD_JSON: &str = include_str!("../../fixtures/leaderboard_2023_later.json");
+
+    #[test]
+    fn test_record_and_query_snapshots() {
+        let dir = temp_dir().join(format!("aoc-friend-snapshots-{}", std::process::id()));
+        let store = SnapshotStore::new(&dir);
+
+        // Record a snapshot every hour
+        let start = Utc.with_ymd_and_hms(2023, 12, 1, 5, 0, 0).unwrap();
+        for hour in 0..5 {
+            store
+                .record(&LeaderboardCacheEntry {
+                    leaderboard: serde_json::from_str(LEADERBOARD_JSON).unwrap(),
+                    leaderboard_id: "1234".to_owned(),
+                    created_at: start + Duration::hours(hour),
+                })
+                .unwrap();
+        }
+
+        let snapshots = store
+            .query(
+                "2023",
+                "1234",
+                start + Duration::hours(1)..start + Duration::hours(3),
+            )
+            .unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>(),
+            vec![start + Duration::hours(1), start + Duration::hours(2)]
+        );
+        assert_eq!(snapshots[0].leaderboard.members.len(), 4);
+
+        let latest = store.latest("2023", "1234").unwrap().unwrap();
+        assert_eq!(latest.created_at, start + Duration::hours(4));
+        assert!(store.latest("2022", "1234").unwrap().is_none());
+        assert_eq!(store.leaderboards().unwrap().len(), 1);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_combined_snapshots() {
+        let dir = temp_dir().join(format!("aoc-friend-combined-{}", std::process::id()));
+        let store = SnapshotStore::new(&dir);
+        let record = |leaderboard_id: &str, json: &str, created_at| {
+            store
+                .record(&LeaderboardCacheEntry {
+                    leaderboard: serde_json::from_str(json).unwrap(),
+                    leaderboard_id: leaderboard_id.to_owned(),
+                    created_at,
+                })
+                .unwrap()
+        };
+
+        // The second leaderboard is first saved an hour after the first
+        let start = Utc.with_ymd_and_hms(2023, 12, 1, 5, 0, 0).unwrap();
+        record("1", LEADERBOARD_JSON, start);
+        record("2", LATER_LEADERBOARD_JSON, start + Duration::hours(1));
+        record("1", LEADERBOARD_JSON, start + Duration::hours(2));
+        record("2", LATER_LEADERBOARD_JSON, start + Duration::hours(3));
+
+        // So they're only combined from then
+        let snapshots = store.query_combined("2023", &["1", "2"], ..).unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>(),
+            vec![
+                start + Duration::hours(1),
+                start + Duration::hours(2),
+                start + Duration::hours(3)
+            ]
+        );
+        assert!(snapshots.iter().all(|s| s.leaderboard_id == "1,2"));
+
+        // And only snapshots within the range are used
+        let snapshots = store
+            .query_combined("2023", &["1", "2"], start + Duration::hours(2)..)
+            .unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>(),
+            vec![start + Duration::hours(3)]
+        );
+
+        // A single leaderboard is just queried
+        assert_eq!(store.query_combined("2023", &["1"], ..).unwrap().len(), 2);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_old_snapshots_are_pruned() {
+        let dir = temp_dir().join(format!("aoc-friend-pruning-{}", std::process::id()));
+        let store = SnapshotStore::new(&dir).with_retention(Duration::hours(2));
+
+        // Record a snapshot every hour, keeping the last two hours
+        let start = Utc.with_ymd_and_hms(2023, 12, 1, 5, 0, 0).unwrap();
+        for hour in 0..5 {
+            store
+                .record(&LeaderboardCacheEntry {
+                    leaderboard: serde_json::from_str(LEADERBOARD_JSON).unwrap(),
+                    leaderboard_id: "1234".to_owned(),
+                    created_at: start + Duration::hours(hour),
+                })
+                .unwrap();
+        }
+
+        let snapshots = store.query("2023", "1234", ..).unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>(),
+            vec![
+                start + Duration::hours(2),
+                start + Duration::hours(3),
+                start + Duration::hours(4)
+            ]
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+}
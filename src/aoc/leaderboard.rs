@@ -1,10 +1,20 @@
-use std::{collections::HashMap, error::Error};
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, RandomState},
+    sync::OnceLock,
+};
 
-use chrono::Utc;
-use reqwest::StatusCode;
-use serde::Deserialize;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
-use super::CACHE_TTL_SECS;
+use super::{
+    error::{check_status, AocError},
+    CACHE_TTL_SECS,
+};
+
+/// Separates the IDs of leaderboards that are combined into one
+pub const COMBINED_ID_SEPARATOR: char = ',';
 
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct LeaderboardCacheKey(String, String);
@@ -22,60 +32,122 @@ pub struct LeaderboardCacheEntry {
 
 impl LeaderboardCacheEntry {
     pub fn is_expired(&self) -> bool {
+        self.is_older_than(CACHE_TTL_SECS)
+    }
+
+    pub fn is_older_than(&self, secs: i64) -> bool {
         Utc::now()
             .signed_duration_since(self.created_at)
             .num_seconds()
-            > CACHE_TTL_SECS
+            > secs
+    }
+}
+
+/// How to authenticate when fetching a private leaderboard
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardAuth {
+    /// A user's session cookie, which grants access to every leaderboard they are a member of
+    SessionToken(String),
+
+    /// A read-only key shared by the owner of a leaderboard
+    ViewKey(String),
+}
+
+impl LeaderboardAuth {
+    /// Something to tell credentials apart by, so they can be kept track of without holding
+    /// onto the token or key itself. It's hashed with a key picked when the bot starts, so it
+    /// can't be matched to credentials outside of this process.
+    pub fn fingerprint(&self) -> AuthFingerprint {
+        static HASHER: OnceLock<RandomState> = OnceLock::new();
+        AuthFingerprint(HASHER.get_or_init(RandomState::new).hash_one(self))
+    }
+}
+
+/// A hash of some credentials, see `LeaderboardAuth::fingerprint`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AuthFingerprint(u64);
+
+// Credentials are left out so they can't end up in logs
+impl fmt::Debug for LeaderboardAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaderboardAuth::SessionToken(_) => write!(f, "SessionToken(<redacted>)"),
+            LeaderboardAuth::ViewKey(_) => write!(f, "ViewKey(<redacted>)"),
+        }
     }
 }
 
-#[allow(dead_code)]
-#[derive(Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CompletionDayLevelEntry {
     pub star_index: usize,
     pub get_star_ts: usize,
 }
 
+impl CompletionDayLevelEntry {
+    /// When the star was earned
+    pub fn earned_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.get_star_ts as i64, 0)
+            .single()
+            .unwrap_or_default()
+    }
+}
+
 pub async fn fetch_leaderboard(
     client: &reqwest::Client,
+    base_url: &str,
     event_id: &str,
     leaderboard_id: &str,
-    session_token: &str,
-) -> Result<Leaderboard, Box<dyn Error>> {
+    auth: &LeaderboardAuth,
+) -> Result<Leaderboard, AocError> {
     // Fetch
-    let res = client
-        .get(format!(
-            "https://adventofcode.com/{event_id}/leaderboard/private/view/{leaderboard_id}.json"
-        ))
-        .header("cookie", format!("session={session_token}"))
-        .send()
-        .await?;
+    let request = client.get(format!(
+        "{base_url}/{event_id}/leaderboard/private/view/{leaderboard_id}.json"
+    ));
+    let request = match auth {
+        LeaderboardAuth::SessionToken(token) => {
+            request.header("cookie", format!("session={token}"))
+        }
+        LeaderboardAuth::ViewKey(key) => request.query(&[("view_key", key)]),
+    };
+    let res = request.send().await?;
 
     // Check that the request was successfull
-    if res.status() == StatusCode::NOT_FOUND {
-        return Err("No such leaderboard found".to_owned().into());
+    let res = check_status(res).map_err(|err| reject_auth(err, auth))?;
+
+    // AoC serves a html page instead of JSON when the session isn't valid
+    let body = res.text().await?;
+    if !body.trim_start().starts_with('{') {
+        return Err(reject_auth(AocError::SessionExpired, auth));
     }
 
     // Parse
-    let leaderboard = res.json().await?;
+    let leaderboard = serde_json::from_str(&body)?;
 
     // Return result
     Ok(leaderboard)
 }
 
-#[derive(Deserialize)]
+/// View keys are rejected the same way as sessions, but need a different fix
+fn reject_auth(err: AocError, auth: &LeaderboardAuth) -> AocError {
+    match (err, auth) {
+        (AocError::SessionExpired | AocError::Forbidden, LeaderboardAuth::ViewKey(_)) => {
+            AocError::ViewKeyRejected
+        }
+        (err, _) => err,
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Leaderboard {
     /// What event this leaderboard is for. Typically this is the year (e.g 2020)
     pub event: String,
 
-    /// ID of the user that owns this leaderboard
-    pub owner_id: usize,
-
     /// Members and their leaderboard values
     pub members: HashMap<String, LeaderboardMember>,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LeaderboardMember {
     /// Name of user
     pub name: Option<String>,
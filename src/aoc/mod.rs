@@ -1,73 +1,408 @@
+mod api;
+mod cache;
+mod error;
+mod governor;
+mod history;
 mod leaderboard;
 mod puzzle;
 
-use std::{collections::HashMap, error::Error, sync::Arc};
+use std::sync::Arc;
 
 use chrono::Utc;
-use reqwest::Client;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::broadcast;
 
-pub use leaderboard::{fetch_leaderboard, Leaderboard, LeaderboardCacheEntry, LeaderboardCacheKey};
-pub use puzzle::{fetch_puzzle_details, PuzzleDetails, PuzzleKey};
+use crate::scoring::merge_leaderboards;
+
+#[cfg(test)]
+pub use api::FixtureAocApi;
+pub use api::{AocApi, HttpAocApi};
+pub use error::AocError;
+pub use governor::{RequestGovernor, MIN_LEADERBOARD_INTERVAL_SECS};
+pub use history::SnapshotStore;
+pub use leaderboard::{
+    AuthFingerprint, Leaderboard, LeaderboardAuth, LeaderboardCacheEntry, LeaderboardCacheKey,
+    LeaderboardMember, COMBINED_ID_SEPARATOR,
+};
+pub use puzzle::{PuzzleDetails, PuzzleKey};
+
+use cache::FetchCache;
 
 const CACHE_TTL_SECS: i64 = 900;
 
-pub struct AOCData {
-    leaderboards: HashMap<LeaderboardCacheKey, Arc<LeaderboardCacheEntry>>,
-    http_client: Client,
-    puzzles: HashMap<PuzzleKey, PuzzleDetails>,
+/// How old an expired leaderboard can be and still be returned straight away, while a fresh one
+/// is fetched in the background
+const MAX_STALE_SECS: i64 = 60 * 60;
+
+/// How many refreshed leaderboards a slow subscriber can fall behind by before missing some
+const REFRESH_CHANNEL_CAPACITY: usize = 32;
+
+/// Leaderboards and puzzles from AoC, cached so they're only fetched when needed.
+/// Every method takes `&self`, so it can be shared without a lock and lookups never wait on
+/// each other unless they're for the same thing.
+pub struct AOCData<A = RequestGovernor> {
+    /// Only requests with the same credentials share a fetch, so new credentials are always tried
+    leaderboards: FetchCache<LeaderboardCacheKey, Arc<LeaderboardCacheEntry>, AuthFingerprint>,
+    api: Arc<A>,
+    puzzles: FetchCache<PuzzleKey, PuzzleDetails>,
+    history: Option<SnapshotStore>,
+    refreshes: broadcast::Sender<Arc<LeaderboardCacheEntry>>,
 }
 
 impl AOCData {
     pub fn new() -> Self {
+        Self::with_api(RequestGovernor::from_env(HttpAocApi::from_env()))
+    }
+}
+
+impl<A: AocApi + 'static> AOCData<A> {
+    pub fn with_api(api: A) -> Self {
         Self {
-            http_client: reqwest::Client::new(),
-            leaderboards: HashMap::new(),
-            puzzles: HashMap::new(),
+            api: Arc::new(api),
+            leaderboards: FetchCache::new(),
+            puzzles: FetchCache::new(),
+            history: None,
+            refreshes: broadcast::channel(REFRESH_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Receive every leaderboard fetched from AoC (cache hits aren't sent)
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LeaderboardCacheEntry>> {
+        self.refreshes.subscribe()
+    }
+
+    /// Record every fetched leaderboard in a snapshot store
+    pub fn with_history(mut self, history: SnapshotStore) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn history(&self) -> Option<&SnapshotStore> {
+        self.history.as_ref()
+    }
+
+    #[cfg(test)]
+    pub fn api(&self) -> &A {
+        &self.api
+    }
+
+    /// Fill the leaderboard cache with the latest snapshot of each leaderboard in the history.
+    /// Snapshots older than the cache TTL will be refetched as usual when next requested.
+    pub fn restore_from_history(&self) -> std::io::Result<usize> {
+        let Some(history) = &self.history else {
+            return Ok(0);
+        };
+
+        let mut restored = 0;
+        for (event_id, leaderboard_id) in history.leaderboards()? {
+            if let Some(entry) = history.latest(&event_id, &leaderboard_id)? {
+                self.leaderboards.insert(
+                    LeaderboardCacheKey::new(&event_id, &leaderboard_id),
+                    Arc::new(entry),
+                );
+                restored += 1;
+            }
         }
+        Ok(restored)
     }
 
     pub async fn get_puzzle_details(
-        &mut self,
+        &self,
         year: usize,
         day: usize,
-    ) -> Result<PuzzleDetails, Box<dyn Error>> {
+    ) -> Result<PuzzleDetails, AocError> {
         let key = (year, day);
         match self.puzzles.get(&key) {
-            Some(puzzle) => Ok(puzzle.clone()),
+            Some(puzzle) => Ok(puzzle),
             _ => {
-                let puzzle_details = fetch_puzzle_details(&self.http_client, year, day).await;
-                puzzle_details.map(|pd| {
-                    self.puzzles.insert(key, pd.clone());
-                    pd
-                })
+                let api = self.api.clone();
+                self.puzzles
+                    .fetch(key, (), || {
+                        async move { api.fetch_puzzle_details(year, day).await }.boxed()
+                    })
+                    .await
             }
         }
     }
 
     pub async fn get_leaderboard(
-        &mut self,
+        &self,
         event_id: &str,
         leaderboard_id: &str,
-        session_token: &str,
+        auth: &LeaderboardAuth,
         skip_cache: bool,
-    ) -> Result<Arc<LeaderboardCacheEntry>, Box<dyn Error>> {
+    ) -> Result<Arc<LeaderboardCacheEntry>, AocError> {
         let key = LeaderboardCacheKey::new(event_id, leaderboard_id);
+        let fetch = || self.fetch_leaderboard(event_id, leaderboard_id, auth);
         match self.leaderboards.get(&key) {
             // If we have an unexpired cache entry, return it
-            Some(entry) if !entry.is_expired() && !skip_cache => Ok(entry.clone()),
-
-            // Otherwise, fetch and then cache it
-            _ => fetch_leaderboard(&self.http_client, event_id, leaderboard_id, session_token)
-                .await
-                .map(|leaderboard| {
-                    let entry = Arc::new(LeaderboardCacheEntry {
-                        leaderboard,
-                        leaderboard_id: leaderboard_id.to_owned(),
-                        created_at: Utc::now(),
-                    });
-                    self.leaderboards.insert(key, entry.clone());
-                    entry
-                }),
+            Some(entry) if !entry.is_expired() && !skip_cache => Ok(entry),
+
+            // If it's only a little out of date, return it while a fresh one is fetched
+            Some(entry) if !entry.is_older_than(MAX_STALE_SECS) && !skip_cache => {
+                self.leaderboards.refresh(key, auth.fingerprint(), fetch);
+                Ok(entry)
+            }
+
+            // Otherwise, fetch it (or wait for the fetch that's already happening)
+            cached => match (
+                self.leaderboards
+                    .fetch(key, auth.fingerprint(), fetch)
+                    .await,
+                cached,
+            ) {
+                // If AoC can't be asked again yet, make do with what we have
+                (Err(AocError::TooSoon(_)), Some(entry)) if !skip_cache => Ok(entry),
+                (result, _) => result,
+            },
+        }
+    }
+
+    /// Make sure the cached copy of a leaderboard is up to date. Unlike `get_leaderboard`, an
+    /// expired copy isn't returned while it's refreshed, so failed fetches are reported.
+    pub async fn refresh_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        auth: &LeaderboardAuth,
+    ) -> Result<Arc<LeaderboardCacheEntry>, AocError> {
+        let key = LeaderboardCacheKey::new(event_id, leaderboard_id);
+        match self.leaderboards.get(&key) {
+            Some(entry) if !entry.is_expired() => Ok(entry),
+            cached => match (
+                self.leaderboards
+                    .fetch(key, auth.fingerprint(), || {
+                        self.fetch_leaderboard(event_id, leaderboard_id, auth)
+                    })
+                    .await,
+                cached,
+            ) {
+                // Someone else fetched it recently, so it's as fresh as AoC allows
+                (Err(AocError::TooSoon(_)), Some(entry)) => Ok(entry),
+                (result, _) => result,
+            },
+        }
+    }
+
+    /// Fetch a leaderboard from AoC, then save it to the history and tell subscribers about it
+    fn fetch_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_id: &str,
+        auth: &LeaderboardAuth,
+    ) -> BoxFuture<'static, Result<Arc<LeaderboardCacheEntry>, AocError>> {
+        let api = self.api.clone();
+        let history = self.history.clone();
+        let refreshes = self.refreshes.clone();
+        let (event_id, leaderboard_id, auth) =
+            (event_id.to_owned(), leaderboard_id.to_owned(), auth.clone());
+        async move {
+            let leaderboard = api
+                .fetch_leaderboard(&event_id, &leaderboard_id, &auth)
+                .await?;
+            let entry = Arc::new(LeaderboardCacheEntry {
+                leaderboard,
+                leaderboard_id,
+                created_at: Utc::now(),
+            });
+
+            // Keep a copy of every fetch on disk
+            if let Some(history) = &history {
+                if let Err(err) = history.record(&entry) {
+                    log::warn!("Failed to record leaderboard snapshot: {err}");
+                }
+            }
+
+            // Let subscribers know, it doesn't matter if there aren't any
+            refreshes.send(entry.clone()).ok();
+
+            Ok(entry)
+        }
+        .boxed()
+    }
+
+    /// Get several leaderboards merged into one, as if all their members were on the same
+    /// leaderboard. Each is fetched and cached on its own, so only the merged result is new.
+    pub async fn get_combined_leaderboard(
+        &self,
+        event_id: &str,
+        leaderboard_ids: &[&str],
+        auth: &LeaderboardAuth,
+        skip_cache: bool,
+    ) -> Result<Arc<LeaderboardCacheEntry>, AocError> {
+        match leaderboard_ids {
+            [] => Err(AocError::NotFound),
+            [leaderboard_id] => {
+                self.get_leaderboard(event_id, leaderboard_id, auth, skip_cache)
+                    .await
+            }
+            _ => {
+                let entries =
+                    futures::future::try_join_all(leaderboard_ids.iter().map(|leaderboard_id| {
+                        self.get_leaderboard(event_id, leaderboard_id, auth, skip_cache)
+                    }))
+                    .await?;
+                let leaderboards: Vec<_> = entries.iter().map(|entry| &entry.leaderboard).collect();
+                Ok(Arc::new(LeaderboardCacheEntry {
+                    leaderboard: merge_leaderboards(&leaderboards),
+                    leaderboard_id: leaderboard_ids.join(&COMBINED_ID_SEPARATOR.to_string()),
+                    created_at: entries
+                        .iter()
+                        .map(|entry| entry.created_at)
+                        .min()
+                        .unwrap_or_else(Utc::now),
+                }))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use chrono::Utc;
+
+    use super::{
+        AOCData, FixtureAocApi, LeaderboardAuth, LeaderboardCacheEntry, LeaderboardCacheKey,
+    };
+
+    const LEADERBOARD_JSON: &str = include_str!("../../fixtures/leaderboard_2023.json");
+
+    #[tokio::test]
+    async fn test_leaderboard_is_cached() {
+        let api = FixtureAocApi::default().with_leaderboard("2023", "1234", LEADERBOARD_JSON);
+        let aoc_data = AOCData::with_api(api);
+        let mut refreshes = aoc_data.subscribe();
+        let auth = LeaderboardAuth::ViewKey("key".to_owned());
+
+        let first = aoc_data
+            .get_leaderboard("2023", "1234", &auth, false)
+            .await
+            .unwrap();
+        assert_eq!(first.leaderboard.members.len(), 4);
+
+        // Second request should be served from the cache
+        aoc_data
+            .get_leaderboard("2023", "1234", &auth, false)
+            .await
+            .unwrap();
+        assert_eq!(aoc_data.api.request_count(), 1);
+
+        // Unless we ask to skip it
+        aoc_data
+            .get_leaderboard("2023", "1234", &auth, true)
+            .await
+            .unwrap();
+        assert_eq!(aoc_data.api.request_count(), 2);
+
+        // Only the two fetches are sent to subscribers
+        assert_eq!(refreshes.len(), 2);
+        assert_eq!(refreshes.try_recv().unwrap().leaderboard_id, "1234");
+    }
+
+    #[tokio::test]
+    async fn test_combined_leaderboard() {
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+            .with_leaderboard("2023", "2", LEADERBOARD_JSON);
+        let aoc_data = AOCData::with_api(api);
+        let auth = LeaderboardAuth::SessionToken("token".to_owned());
+
+        let combined = aoc_data
+            .get_combined_leaderboard("2023", &["1", "2"], &auth, false)
+            .await
+            .unwrap();
+        assert_eq!(combined.leaderboard_id, "1,2");
+        assert_eq!(combined.leaderboard.members.len(), 4);
+
+        // The boards are cached on their own
+        aoc_data
+            .get_leaderboard("2023", "2", &auth, false)
+            .await
+            .unwrap();
+        assert_eq!(aoc_data.api.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_puzzle_is_not_cached() {
+        let api = FixtureAocApi::default().with_puzzle(2022, 1, "Calorie Counting");
+        let aoc_data = AOCData::with_api(api);
+
+        assert!(aoc_data.get_puzzle_details(2022, 2).await.is_err());
+        let puzzle = aoc_data.get_puzzle_details(2022, 1).await.unwrap();
+        assert_eq!(puzzle.name, "Calorie Counting");
+        aoc_data.get_puzzle_details(2022, 1).await.unwrap();
+        assert_eq!(aoc_data.api.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_a_fetch() {
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1234", LEADERBOARD_JSON)
+            .with_delay(Duration::from_millis(50));
+        let aoc_data = AOCData::with_api(api);
+        let auth = LeaderboardAuth::ViewKey("key".to_owned());
+
+        let (first, second) = tokio::join!(
+            aoc_data.get_leaderboard("2023", "1234", &auth, false),
+            aoc_data.get_leaderboard("2023", "1234", &auth, false),
+        );
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+        assert_eq!(aoc_data.api.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_credentials_dont_share_a_fetch() {
+        let expired = LeaderboardAuth::SessionToken("expired".to_owned());
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1234", LEADERBOARD_JSON)
+            .with_rejected_auth(expired.clone())
+            .with_delay(Duration::from_millis(50));
+        let aoc_data = AOCData::with_api(api);
+        let auth = LeaderboardAuth::SessionToken("token".to_owned());
+
+        // New credentials are tried even while old ones are still being fetched with
+        let (first, second) = tokio::join!(
+            aoc_data.get_leaderboard("2023", "1234", &expired, false),
+            aoc_data.get_leaderboard("2023", "1234", &auth, true),
+        );
+        assert!(first.is_err());
+        assert_eq!(second.unwrap().leaderboard.members.len(), 4);
+        assert_eq!(aoc_data.api.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_leaderboard_is_refreshed_in_background() {
+        let api = FixtureAocApi::default().with_leaderboard("2023", "1234", LEADERBOARD_JSON);
+        let aoc_data = AOCData::with_api(api);
+        let mut refreshes = aoc_data.subscribe();
+        let auth = LeaderboardAuth::ViewKey("key".to_owned());
+
+        // Cache a leaderboard that has just expired
+        let stale = Arc::new(LeaderboardCacheEntry {
+            leaderboard: serde_json::from_str(LEADERBOARD_JSON).unwrap(),
+            leaderboard_id: "1234".to_owned(),
+            created_at: Utc::now() - chrono::Duration::minutes(20),
+        });
+        aoc_data
+            .leaderboards
+            .insert(LeaderboardCacheKey::new("2023", "1234"), stale.clone());
+
+        // It's returned straight away
+        let entry = aoc_data
+            .get_leaderboard("2023", "1234", &auth, false)
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&entry, &stale));
+
+        // And replaced once the refresh finishes
+        let fresh = refreshes.recv().await.unwrap();
+        let entry = aoc_data
+            .get_leaderboard("2023", "1234", &auth, false)
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&entry, &fresh));
+        assert_eq!(aoc_data.api.request_count(), 1);
+    }
+}
@@ -1,8 +1,6 @@
-use reqwest::StatusCode;
 use serde::Deserialize;
-use std::error::Error;
 
-use crate::format::generate_puzzle_url;
+use super::error::{check_status, AocError};
 
 /// Unique identifier for a puzzle, consists of (year, day)
 pub type PuzzleKey = (usize, usize);
@@ -16,20 +14,28 @@ pub struct PuzzleDetails {
 
 pub async fn fetch_puzzle_details(
     client: &reqwest::Client,
+    base_url: &str,
     year: usize,
     day: usize,
-) -> Result<PuzzleDetails, Box<dyn Error>> {
+) -> Result<PuzzleDetails, AocError> {
     // Fetch puzzle page
-    let res = client.get(generate_puzzle_url(year, day)).send().await?;
+    let res = client
+        .get(format!("{base_url}/{year}/day/{day}"))
+        .send()
+        .await?;
 
     // Check that the request was successfull
-    if res.status() == StatusCode::NOT_FOUND {
-        return Err("No such puzzle found".to_owned().into());
-    }
+    let res = check_status(res)?;
 
     // Parse the html response
     let html = res.text().await?;
-    let dom = tl::parse(&html, tl::ParserOptions::default())?;
+    parse_puzzle_details(&html)
+}
+
+/// Extract the puzzle details from the html of a puzzle page
+pub fn parse_puzzle_details(html: &str) -> Result<PuzzleDetails, AocError> {
+    let dom = tl::parse(html, tl::ParserOptions::default())
+        .map_err(|err| AocError::Schema(err.to_string()))?;
     let parser = dom.parser();
 
     // Get the heading name from the <h2/>
@@ -37,7 +43,7 @@ pub async fn fetch_puzzle_details(
         .query_selector("h2")
         .expect("Should succesfully parse the query selector")
         .next()
-        .ok_or("Couldn't find heading element")?;
+        .ok_or_else(|| AocError::Schema("couldn't find heading element".to_owned()))?;
     let heading = heading_handle
         .get(parser)
         .expect("Handle to resolve to element");
@@ -45,10 +51,12 @@ pub async fn fetch_puzzle_details(
 
     // Extract puzzle name from heading text
     let name = heading_text;
-    let (_, name) = name.split_once(':').expect("Heading text to include a :");
-    let (name, _) = name
-        .split_once('-')
-        .expect("Heading text to include a - after the :");
+    let (_, name) = name
+        .split_once(':')
+        .ok_or_else(|| AocError::Schema("heading text didn't include a :".to_owned()))?;
+    let (name, _) = name.split_once('-').ok_or_else(|| {
+        AocError::Schema("heading text didn't include a - after the :".to_owned())
+    })?;
     let name = name.trim();
 
     Ok(PuzzleDetails {
@@ -58,13 +66,29 @@ pub async fn fetch_puzzle_details(
 
 #[cfg(test)]
 mod test {
-    use super::fetch_puzzle_details;
+    use super::{fetch_puzzle_details, parse_puzzle_details, AocError};
+    use crate::aoc::api::DEFAULT_BASE_URL;
     use reqwest::Client;
 
     #[tokio::test]
     async fn test_fetch_puzzle_details() {
         let client = Client::new();
-        let puzzle = fetch_puzzle_details(&client, 2022, 1).await.unwrap();
+        let puzzle = fetch_puzzle_details(&client, DEFAULT_BASE_URL, 2022, 1)
+            .await
+            .unwrap();
+        assert_eq!(puzzle.name, "Calorie Counting");
+    }
+
+    #[test]
+    fn test_parse_puzzle_details() {
+        let html = include_str!("../../fixtures/puzzle_2022_1.html");
+        let puzzle = parse_puzzle_details(html).unwrap();
         assert_eq!(puzzle.name, "Calorie Counting");
     }
+
+    #[test]
+    fn test_parse_unexpected_page() {
+        let error = parse_puzzle_details("<html><body>Please log in</body></html>").unwrap_err();
+        assert!(matches!(error, AocError::Schema(_)));
+    }
 }
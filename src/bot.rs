@@ -1,30 +1,51 @@
+use chrono::{DateTime, Utc};
 use serenity::{
     async_trait,
     model::{gateway::Ready, id::GuildId, prelude::interaction::Interaction},
     prelude::*,
 };
-use std::{cell::RefCell, env, sync::Arc};
-use tokio::task::JoinHandle;
+use std::{cell::RefCell, env, ops::RangeBounds, sync::Arc};
+use tokio::{sync::Notify, task::JoinHandle};
 
 use crate::{
-    aoc::{AOCData, LeaderboardCacheEntry},
-    commands,
-    config::Config,
-    daily,
+    aoc::{AOCData, LeaderboardCacheEntry, SnapshotStore},
+    commands, daily, notify, prefetch, rewards,
+    storage::{self, SharedStorage},
 };
 
 pub struct Bot {
-    pub aoc_data: Arc<Mutex<AOCData>>,
+    pub aoc_data: Arc<AOCData>,
+    pub storage: SharedStorage,
     pub daily_thread: Mutex<RefCell<Option<JoinHandle<()>>>>,
+    pub notify_thread: Mutex<RefCell<Option<JoinHandle<()>>>>,
+    pub rewards_thread: Mutex<RefCell<Option<JoinHandle<()>>>>,
+    pub prefetch_thread: Mutex<RefCell<Option<JoinHandle<()>>>>,
+    /// Wakes the daily posting thread when a daily is added or changed
+    pub daily_changes: Arc<Notify>,
 }
 
 impl Bot {
     pub async fn start(token: String) {
+        // Rebuild the leaderboard cache from snapshots saved before we last stopped
+        let aoc_data = AOCData::new().with_history(SnapshotStore::from_env());
+        match aoc_data.restore_from_history() {
+            Ok(restored) => log::info!("Restored {restored} leaderboards from snapshot history"),
+            Err(err) => log::warn!("Failed to restore leaderboards from snapshot history: {err}"),
+        }
+
+        // Open storage, bringing across anything from an old config file
+        let storage = storage::open_from_env().expect("Failed to open storage");
+
         // Create client
         let mut client = Client::builder(token, GatewayIntents::empty())
             .event_handler(Bot {
-                aoc_data: Arc::new(Mutex::new(AOCData::new())),
+                aoc_data: Arc::new(aoc_data),
+                storage,
                 daily_thread: Mutex::new(RefCell::new(None)),
+                notify_thread: Mutex::new(RefCell::new(None)),
+                rewards_thread: Mutex::new(RefCell::new(None)),
+                prefetch_thread: Mutex::new(RefCell::new(None)),
+                daily_changes: Arc::new(Notify::new()),
             })
             .await
             .expect("to create client");
@@ -35,49 +56,97 @@ impl Bot {
         }
     }
 
+    /// Get one of a guild's registered leaderboards by name, or its default leaderboard
     pub async fn get_registered_leaderboard(
         &self,
         guild_id: GuildId,
+        board: Option<&str>,
         year: i32,
     ) -> Result<Arc<LeaderboardCacheEntry>, String> {
         // Get config for guild
-        let config = Config::get().expect("Failed to load config");
-        let guild_config = config
-            .guild_configs
-            .get(&guild_id)
-            .ok_or_else(|| "server has no registered leaderboard".to_owned())?;
+        let guild_config = self
+            .storage
+            .guild_config(guild_id, board)
+            .expect("Failed to load config")
+            .ok_or_else(|| match board {
+                Some(board) => format!(
+                    "This server has no leaderboard called `{board}`. You can see every registered leaderboard with `/status`."
+                ),
+                None => "This server has no registered leaderboard. You can set one up with `/register`."
+                    .to_owned(),
+            })?;
 
         // Get leaderboard
-        let leaderboard = {
-            let mut aoc_data = self.aoc_data.lock().await;
-            aoc_data
-                .get_leaderboard(
-                    &year.to_string(),
-                    &guild_config.leaderboard_id,
-                    &guild_config.session_token,
-                    false,
-                )
-                .await
-        };
-        leaderboard.map_err(|e| e.to_string())
+        let leaderboard = self
+            .aoc_data
+            .get_combined_leaderboard(
+                &year.to_string(),
+                &guild_config.leaderboard_ids(),
+                &guild_config.auth,
+                false,
+            )
+            .await;
+        leaderboard.map_err(|e| {
+            log::warn!("Failed to fetch leaderboard for guild {guild_id}: {e}");
+            e.user_message()
+        })
+    }
+
+    /// Get every stored snapshot of one of a guild's registered leaderboards taken within a
+    /// time range, oldest first
+    pub fn get_registered_leaderboard_history(
+        &self,
+        guild_id: GuildId,
+        board: Option<&str>,
+        year: i32,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> Result<Vec<LeaderboardCacheEntry>, String> {
+        // Get config for guild
+        let guild_config = self
+            .storage
+            .guild_config(guild_id, board)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "This server has no registered leaderboard.".to_owned())?;
+
+        // Query the snapshots
+        let history = self
+            .aoc_data
+            .history()
+            .ok_or_else(|| "Leaderboard history is not enabled.".to_owned())?;
+        history
+            .query_combined(&year.to_string(), &guild_config.leaderboard_ids(), range)
+            .map_err(|e| e.to_string())
     }
 }
 
 #[async_trait]
 impl EventHandler for Bot {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        // If this interaction is a command
-        if let Interaction::ApplicationCommand(command) = interaction {
-            match command.data.name.as_str() {
+        match interaction {
+            // If this interaction is a command
+            Interaction::ApplicationCommand(command) => match command.data.name.as_str() {
                 "register" => commands::register::run(self, &ctx, &command).await,
                 "unregister" => commands::unregister::run(self, &ctx, &command).await,
                 "leaderboard" => commands::leaderboard::run(self, &ctx, &command).await,
+                "changes" => commands::changes::run(self, &ctx, &command).await,
                 "puzzle" => commands::puzzle::run(self, &ctx, &command).await,
                 "daily" => commands::daily::run(self, &ctx, &command).await,
+                "notify" => commands::notify::run(self, &ctx, &command).await,
+                "reward" => commands::reward::run(self, &ctx, &command).await,
                 "status" => commands::status::run(self, &ctx, &command).await,
+                "link" => commands::link::run(self, &ctx, &command).await,
+                "unlink" => commands::unlink::run(self, &ctx, &command).await,
+                "timezone" => commands::timezone::run(self, &ctx, &command).await,
                 "help" => commands::help::run(self, &ctx, &command).await,
                 _ => {}
+            },
+
+            // If the user is typing an option with suggestions
+            Interaction::Autocomplete(autocomplete) => {
+                commands::autocomplete(self, &ctx, &autocomplete).await
             }
+
+            _ => {}
         }
     }
 
@@ -102,21 +171,68 @@ impl EventHandler for Bot {
                 .create_application_command(commands::register::register)
                 .create_application_command(commands::unregister::register)
                 .create_application_command(commands::leaderboard::register)
+                .create_application_command(commands::changes::register)
                 .create_application_command(commands::puzzle::register)
                 .create_application_command(commands::daily::register)
+                .create_application_command(commands::notify::register)
+                .create_application_command(commands::reward::register)
                 .create_application_command(commands::status::register)
+                .create_application_command(commands::link::register)
+                .create_application_command(commands::unlink::register)
+                .create_application_command(commands::timezone::register)
                 .create_application_command(commands::help::register)
         })
         .await
         .expect("to have created guild commands");
 
+        // Start leaderboard prefetching thread
+        {
+            let mut prefetch_thread = self.prefetch_thread.lock().await;
+            prefetch_thread.get_mut().get_or_insert_with(|| {
+                tokio::spawn(prefetch::prefetch_leaderboards(
+                    self.aoc_data.clone(),
+                    self.storage.clone(),
+                ))
+            });
+        }
+
+        // Start star notification thread
+        {
+            let mut notify_thread = self.notify_thread.lock().await;
+            notify_thread.get_mut().get_or_insert_with(|| {
+                tokio::spawn(notify::star_notifications(
+                    self.aoc_data.clone(),
+                    self.storage.clone(),
+                    ctx.clone(),
+                ))
+            });
+        }
+
+        // Start reward role thread
+        {
+            let refreshes = self.aoc_data.subscribe();
+            let mut rewards_thread = self.rewards_thread.lock().await;
+            rewards_thread.get_mut().get_or_insert_with(|| {
+                tokio::spawn(rewards::role_rewards(
+                    self.aoc_data.clone(),
+                    self.storage.clone(),
+                    ctx.clone(),
+                    refreshes,
+                ))
+            });
+        }
+
         // Start daily posting thread
-        let mut daily_thread = self.daily_thread.lock().await;
-        let daily_thread = daily_thread.get_mut();
-        let daily_thread = daily_thread.get_or_insert(tokio::spawn(daily::daily_posts(
-            self.aoc_data.clone(),
-            ctx.clone(),
-        )));
-        daily_thread.await.unwrap();
+        {
+            let mut daily_thread = self.daily_thread.lock().await;
+            daily_thread.get_mut().get_or_insert_with(|| {
+                tokio::spawn(daily::daily_posts(
+                    self.aoc_data.clone(),
+                    self.storage.clone(),
+                    ctx.clone(),
+                    self.daily_changes.clone(),
+                ))
+            });
+        }
     }
 }
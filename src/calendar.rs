@@ -0,0 +1,151 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Utc};
+
+/// The offset of EST from UTC, which AoC releases puzzles in all year round
+pub const EST_SECS: i32 = -5 * 60 * 60;
+
+/// The year of the first event
+pub const FIRST_YEAR: i32 = 2015;
+
+/// The most puzzles any event has had
+pub const MAX_PUZZLE_COUNT: u32 = 25;
+
+/// The timezone AoC releases puzzles in
+pub fn est() -> FixedOffset {
+    FixedOffset::east_opt(EST_SECS).unwrap()
+}
+
+/// The dates of one year's Advent of Code event. A puzzle unlocks at midnight EST every day from
+/// the 1st of December, and the event ends when the day of its last puzzle does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventCalendar {
+    year: i32,
+}
+
+impl EventCalendar {
+    pub fn new(year: i32) -> Self {
+        Self { year }
+    }
+
+    /// The latest event to have started by a time. The year changes over at midnight EST on the
+    /// 1st of December, when the first puzzle unlocks.
+    pub fn current(time: DateTime<Utc>) -> Self {
+        let time = time.with_timezone(&est());
+        if time.month() == 12 {
+            Self::new(time.year())
+        } else {
+            Self::new(time.year() - 1)
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// How many puzzles the event has. Events had 25 puzzles until 2025, which has 12.
+    pub fn puzzle_count(&self) -> u32 {
+        if self.year >= 2025 {
+            12
+        } else {
+            25
+        }
+    }
+
+    /// When the puzzle for a day unlocks, if the event has a puzzle that day
+    pub fn unlock_time(&self, day: u32) -> Option<DateTime<Utc>> {
+        if !(1..=self.puzzle_count()).contains(&day) {
+            return None;
+        }
+        est()
+            .with_ymd_and_hms(self.year, 12, day, 0, 0, 0)
+            .single()
+            .map(|time| time.with_timezone(&Utc))
+    }
+
+    /// When the first puzzle unlocks
+    pub fn start(&self) -> DateTime<Utc> {
+        self.unlock_time(1).expect("every event has a first puzzle")
+    }
+
+    /// When the day of the last puzzle ends
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start() + Duration::days(self.puzzle_count() as i64)
+    }
+
+    /// Whether puzzles are being released on the day of a time
+    pub fn is_running(&self, time: DateTime<Utc>) -> bool {
+        (self.start()..self.end()).contains(&time)
+    }
+
+    /// The last day whose puzzle has unlocked by a time (0 if none have)
+    pub fn latest_unlocked_day(&self, time: DateTime<Utc>) -> u32 {
+        (1..=self.puzzle_count())
+            .take_while(|day| self.unlock_time(*day).is_some_and(|unlock| unlock <= time))
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// The day whose puzzle was released on the day of a time, while the event is running
+    pub fn puzzle_day(&self, time: DateTime<Utc>) -> Option<u32> {
+        self.is_running(time)
+            .then(|| self.latest_unlocked_day(time))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::{est, EventCalendar};
+
+    #[test]
+    fn test_year_changes_at_midnight_est() {
+        let before = est().with_ymd_and_hms(2023, 11, 30, 23, 59, 0).unwrap();
+        let after = est().with_ymd_and_hms(2023, 12, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            EventCalendar::current(before.with_timezone(&Utc)).year(),
+            2022
+        );
+        assert_eq!(
+            EventCalendar::current(after.with_timezone(&Utc)).year(),
+            2023
+        );
+
+        // It's already December in UTC, but not yet in EST
+        let utc = Utc.with_ymd_and_hms(2023, 12, 1, 4, 0, 0).unwrap();
+        assert_eq!(EventCalendar::current(utc).year(), 2022);
+    }
+
+    #[test]
+    fn test_event_lengths() {
+        let time = |year, month, day| {
+            est()
+                .with_ymd_and_hms(year, month, day, 12, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        // Older events had 25 puzzles
+        let event = EventCalendar::new(2023);
+        assert_eq!(event.puzzle_count(), 25);
+        assert_eq!(event.puzzle_day(time(2023, 12, 13)), Some(13));
+        assert_eq!(event.puzzle_day(time(2023, 12, 25)), Some(25));
+        assert_eq!(event.puzzle_day(time(2023, 12, 26)), None);
+        assert_eq!(event.latest_unlocked_day(time(2024, 1, 1)), 25);
+
+        // Newer events have 12
+        let event = EventCalendar::new(2025);
+        assert_eq!(event.puzzle_count(), 12);
+        assert_eq!(event.unlock_time(13), None);
+        assert_eq!(event.puzzle_day(time(2025, 11, 30)), None);
+        assert_eq!(event.puzzle_day(time(2025, 12, 12)), Some(12));
+        assert_eq!(event.puzzle_day(time(2025, 12, 13)), None);
+        assert_eq!(
+            event.end(),
+            est()
+                .with_ymd_and_hms(2025, 12, 13, 0, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        );
+        assert_eq!(event.latest_unlocked_day(time(2025, 11, 30)), 0);
+    }
+}
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Where the time comes from, so code that waits for times can be tested without waiting
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until a time, returning straight away if it has already passed
+    async fn sleep_until(&self, time: DateTime<Utc>);
+}
+
+/// The real time
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, time: DateTime<Utc>) {
+        let wait = (time - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+pub use simulated::SimulatedClock;
+
+#[cfg(test)]
+mod simulated {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use tokio::sync::Notify;
+
+    use super::Clock;
+
+    /// A clock that jumps straight to any time it's asked to wait until, and stops once it's
+    /// asked to wait past its end time
+    pub struct SimulatedClock {
+        now: Mutex<DateTime<Utc>>,
+        end: DateTime<Utc>,
+        finished: Notify,
+    }
+
+    impl SimulatedClock {
+        pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+            Self {
+                now: Mutex::new(start),
+                end,
+                finished: Notify::new(),
+            }
+        }
+
+        /// Wait until something has tried to sleep past the end time
+        pub async fn finished(&self) {
+            self.finished.notified().await
+        }
+    }
+
+    #[async_trait]
+    impl Clock for SimulatedClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.now.lock().unwrap()
+        }
+
+        async fn sleep_until(&self, time: DateTime<Utc>) {
+            if time > self.end {
+                self.finished.notify_one();
+                std::future::pending::<()>().await;
+            }
+            let mut now = self.now.lock().unwrap();
+            *now = (*now).max(time);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::bot::Bot;
+use crate::calendar::EventCalendar;
+use crate::config::LeaderboardOrdering;
+use crate::format::{make_changes_embed, make_message_embed, ResponseReason};
+use crate::links::linked_member_names;
+
+use chrono::{Duration, Utc};
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::prelude::Context;
+
+use super::{
+    add_board_option, add_ordering_choices, extract_int_option, extract_string_option,
+    CommandOptions,
+};
+
+const DEFAULT_HOURS: i64 = 24;
+
+/// How far back changes can be shown, a week
+const MAX_HOURS: i64 = 7 * 24;
+
+// Options //
+
+struct ChangesCommandOptions {
+    hours: i64,
+    ordering: LeaderboardOrdering,
+    board: Option<String>,
+}
+
+impl CommandOptions for ChangesCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            hours: extract_int_option(options_list, "hours")
+                .map(|v| v as i64)
+                .unwrap_or(DEFAULT_HOURS),
+            ordering: extract_string_option(options_list, "ordering")
+                .and_then(|ordering| ordering.parse().ok())
+                .unwrap_or(LeaderboardOrdering::LocalScore),
+            board: extract_string_option(options_list, "board"),
+        }
+    }
+}
+
+// Command //
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Parse command options
+    let options = ChangesCommandOptions::from_options_list(&command.data.options);
+    let guild_id = command.guild_id.expect("command to have guild id");
+    let now = Utc::now();
+    let year = EventCalendar::current(now).year();
+
+    // Defer response
+    command.defer(&ctx.http).await.unwrap();
+
+    // Get the leaderboard now, and the first snapshot of it within the time range
+    let since = now - Duration::hours(options.hours);
+    let leaderboard = bot
+        .get_registered_leaderboard(guild_id, options.board.as_deref(), year)
+        .await;
+    let before = bot
+        .get_registered_leaderboard_history(guild_id, options.board.as_deref(), year, since..)
+        .map(|snapshots| snapshots.into_iter().next());
+    let names = linked_member_names(ctx, &*bot.storage, guild_id).await;
+
+    // Respond
+    let embed = match (before, leaderboard) {
+        (Ok(Some(before)), Ok(leaderboard)) => {
+            make_changes_embed(&before, leaderboard, options.ordering, &names)
+        }
+        (Ok(None), Ok(_)) => make_message_embed(
+            ResponseReason::Error,
+            &format!(
+                "There are no snapshots of this leaderboard from the last {} hours yet, they're saved whenever it's fetched.",
+                options.hours
+            ),
+        ),
+        (Err(error), _) | (_, Err(error)) => make_message_embed(
+            ResponseReason::Error,
+            &format!("Failed to get leaderboard changes. {error}"),
+        ),
+    };
+    command
+        .create_followup_message(&ctx.http, |message| message.add_embed(embed))
+        .await
+        .expect("failed to create interaction response");
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("changes")
+        .description("Shows how the leaderboard has changed recently.")
+        .create_option(|option| {
+            option
+                .name("hours")
+                .description("How many hours back to compare against (default: 24)")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(1)
+                .max_int_value(MAX_HOURS)
+        })
+        .create_option(|option| {
+            option
+                .name("ordering")
+                .description("Ordering to show position changes for (default: local-score)")
+                .kind(CommandOptionType::String);
+            add_ordering_choices(option)
+        })
+        .create_option(add_board_option)
+}
@@ -1,6 +1,7 @@
 use crate::bot::Bot;
-use crate::config::{Config, DailyLeaderboardConfig, LeaderboardOrdering};
+use crate::config::{DailyLeaderboardConfig, LeaderboardOrdering};
 use crate::format::{make_message_embed, ResponseReason};
+use chrono::Utc;
 use serenity::builder::CreateApplicationCommandOption;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
@@ -10,13 +11,16 @@ use serenity::model::prelude::PartialChannel;
 use serenity::prelude::Context;
 
 use super::super::{
+    add_board_option, add_ordering_choices, add_schedule_option, describe_daily_time,
     extract_channel_option, extract_int_option, extract_string_option, CommandOptions,
 };
 
 struct DailyLeaderboardCommandOptions {
     channel: PartialChannel,
     hour: isize,
+    schedule: Option<String>,
     ordering: LeaderboardOrdering,
+    board: Option<String>,
 }
 
 impl CommandOptions for DailyLeaderboardCommandOptions {
@@ -24,15 +28,17 @@ impl CommandOptions for DailyLeaderboardCommandOptions {
         Self {
             channel: extract_channel_option(options_list, "channel").expect("Didn't find channel"),
             hour: extract_int_option(options_list, "hour").unwrap_or(0),
+            schedule: extract_string_option(options_list, "schedule"),
             ordering: extract_string_option(options_list, "ordering")
                 .and_then(|ordering| ordering.parse().ok())
                 .unwrap_or(LeaderboardOrdering::GlobalScore),
+            board: extract_string_option(options_list, "board"),
         }
     }
 }
 
 pub async fn run(
-    _bot: &Bot,
+    bot: &Bot,
     ctx: &Context,
     interaction: &ApplicationCommandInteraction,
     command: &CommandDataOption,
@@ -40,16 +46,45 @@ pub async fn run(
     // Parse options
     let options = DailyLeaderboardCommandOptions::from_options_list(&command.options);
 
+    let schedule = match options.schedule.as_deref().map(str::parse).transpose() {
+        Ok(schedule) => schedule,
+        Err(error) => {
+            interaction
+                .create_interaction_response(&ctx.http, |response| {
+                    response.interaction_response_data(|message| {
+                        message.ephemeral(true).add_embed(make_message_embed(
+                            ResponseReason::Error,
+                            &format!("That schedule isn't valid: {error}."),
+                        ))
+                    })
+                })
+                .await
+                .expect("to respond to command");
+            return;
+        }
+    };
+
     // Save data
-    let mut config = Config::get().expect("Failed to load config");
-    config.daily_leaderboard_configs.insert(
-        options.channel.id,
-        DailyLeaderboardConfig {
-            guild_id: interaction.guild_id.expect("guild id"),
-            hour: options.hour as usize,
-            ordering: options.ordering,
-        },
-    );
+    bot.storage
+        .set_daily_leaderboard_config(
+            options.channel.id,
+            &DailyLeaderboardConfig {
+                guild_id: interaction.guild_id.expect("guild id"),
+                hour: options.hour as usize,
+                schedule: schedule.clone(),
+                // Nothing due before now needs catching up on
+                last_posted: Some(Utc::now()),
+                failures: 0,
+                ordering: options.ordering,
+                board: options.board,
+            },
+        )
+        .expect("Failed to save config");
+    bot.daily_changes.notify_one();
+    let timezone = bot
+        .storage
+        .guild_timezone_or_default(interaction.guild_id.expect("guild id"))
+        .expect("Failed to load config");
 
     // Respond
     interaction
@@ -58,10 +93,10 @@ pub async fn run(
                 message.ephemeral(true).add_embed(make_message_embed(
                     ResponseReason::Success,
                     &format!(
-                        "Successfully registered daily leaderboards to <#{}>. They will be posted at **{}** every day of December.\n\n\
+                        "Successfully registered daily leaderboards to <#{}>. They will be posted {} during December.\n\n\
                         Run this command again to update the settings, or use `/daily unregister leaderboard` to remove this daily.",
                         options.channel.id,
-                        format_args!("{:0>2}:00 EST", options.hour),
+                        describe_daily_time(schedule.as_ref(), options.hour as usize, timezone),
                     ),
                 ))
             })
@@ -85,23 +120,21 @@ pub fn register() -> CreateApplicationCommandOption {
         .create_sub_option(|option| {
             option
                 .name("hour")
-                .description(
-                    "The hour of the day to post the leaderboard in EST (default: midnight)",
-                )
+                .description("The hour of the day to post the leaderboard (default: midnight)")
                 .kind(CommandOptionType::Integer)
                 .min_int_value(0)
                 .max_int_value(23)
         })
+        .create_sub_option(add_schedule_option)
         .create_sub_option(|option| {
             option
                 .name("ordering")
                 .description(
                     "Method used to order people in the leaderboard (default: local-score)",
                 )
-                .kind(CommandOptionType::String)
-                .add_string_choice("local-score", LeaderboardOrdering::LocalScore)
-                .add_string_choice("global-score", LeaderboardOrdering::GlobalScore)
-                .add_string_choice("stars", LeaderboardOrdering::Stars)
+                .kind(CommandOptionType::String);
+            add_ordering_choices(option)
         })
+        .create_sub_option(add_board_option)
         .to_owned()
 }
@@ -1,6 +1,7 @@
 use crate::bot::Bot;
-use crate::config::{Config, DailyPuzzleConfig};
+use crate::config::DailyPuzzleConfig;
 use crate::format::{make_message_embed, ResponseReason};
+use chrono::Utc;
 use serenity::builder::CreateApplicationCommandOption;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
@@ -9,11 +10,15 @@ use serenity::model::prelude::interaction::application_command::{
 use serenity::model::prelude::PartialChannel;
 use serenity::prelude::Context;
 
-use super::super::{extract_channel_option, extract_int_option, CommandOptions};
+use super::super::{
+    add_schedule_option, describe_daily_time, extract_channel_option, extract_int_option,
+    extract_string_option, CommandOptions,
+};
 
 struct DailyPuzzleCommandOptions {
     channel: PartialChannel,
     hour: isize,
+    schedule: Option<String>,
 }
 
 impl CommandOptions for DailyPuzzleCommandOptions {
@@ -21,12 +26,13 @@ impl CommandOptions for DailyPuzzleCommandOptions {
         Self {
             channel: extract_channel_option(options_list, "channel").expect("Didn't find channel"),
             hour: extract_int_option(options_list, "hour").unwrap_or(0),
+            schedule: extract_string_option(options_list, "schedule"),
         }
     }
 }
 
 pub async fn run(
-    _bot: &Bot,
+    bot: &Bot,
     ctx: &Context,
     interaction: &ApplicationCommandInteraction,
     command: &CommandDataOption,
@@ -34,15 +40,43 @@ pub async fn run(
     // Parse options
     let options = DailyPuzzleCommandOptions::from_options_list(&command.options);
 
+    let schedule = match options.schedule.as_deref().map(str::parse).transpose() {
+        Ok(schedule) => schedule,
+        Err(error) => {
+            interaction
+                .create_interaction_response(&ctx.http, |response| {
+                    response.interaction_response_data(|message| {
+                        message.ephemeral(true).add_embed(make_message_embed(
+                            ResponseReason::Error,
+                            &format!("That schedule isn't valid: {error}."),
+                        ))
+                    })
+                })
+                .await
+                .expect("to respond to command");
+            return;
+        }
+    };
+
     // Save data
-    let mut config = Config::get().expect("Failed to load config");
-    config.daily_puzzle_configs.insert(
-        options.channel.id,
-        DailyPuzzleConfig {
-            guild_id: interaction.guild_id.expect("guild id"),
-            hour: options.hour as usize,
-        },
-    );
+    bot.storage
+        .set_daily_puzzle_config(
+            options.channel.id,
+            &DailyPuzzleConfig {
+                guild_id: interaction.guild_id.expect("guild id"),
+                hour: options.hour as usize,
+                schedule: schedule.clone(),
+                // Nothing due before now needs catching up on
+                last_posted: Some(Utc::now()),
+                failures: 0,
+            },
+        )
+        .expect("Failed to save config");
+    bot.daily_changes.notify_one();
+    let timezone = bot
+        .storage
+        .guild_timezone_or_default(interaction.guild_id.expect("guild id"))
+        .expect("Failed to load config");
 
     // Respond
     interaction
@@ -51,9 +85,9 @@ pub async fn run(
                 message.ephemeral(true).add_embed(make_message_embed(
                     ResponseReason::Success,
                     &format!(
-                        "Successfully registered daily puzzles to <#{}>. They will be posted at **{}** every day of December.\n\nRun this command again to update the settings, or use `/daily unregister puzzle` to remove this daily.",
+                        "Successfully registered daily puzzles to <#{}>. They will be posted {} during December.\n\nRun this command again to update the settings, or use `/daily unregister puzzle` to remove this daily.",
                         options.channel.id,
-                        format_args!("{:0>2}:00 EST", options.hour),
+                        describe_daily_time(schedule.as_ref(), options.hour as usize, timezone),
                     ),
                 ))
             })
@@ -77,10 +111,11 @@ pub fn register() -> CreateApplicationCommandOption {
         .create_sub_option(|option| {
             option
                 .name("hour")
-                .description("The hour of the day to post the puzzle in EST (default: midnight)")
+                .description("The hour of the day to post the puzzle (default: midnight)")
                 .kind(CommandOptionType::Integer)
                 .min_int_value(0)
                 .max_int_value(23)
         })
+        .create_sub_option(add_schedule_option)
         .to_owned()
 }
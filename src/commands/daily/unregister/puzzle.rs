@@ -1,5 +1,4 @@
 use crate::bot::Bot;
-use crate::config::Config;
 use crate::format::{make_message_embed, ResponseReason};
 use serenity::builder::CreateApplicationCommandOption;
 use serenity::model::prelude::command::CommandOptionType;
@@ -24,7 +23,7 @@ impl CommandOptions for DailyUnregisterPuzzleCommandOptions {
 }
 
 pub async fn run(
-    _bot: &Bot,
+    bot: &Bot,
     ctx: &Context,
     interaction: &ApplicationCommandInteraction,
     command: &CommandDataOption,
@@ -33,14 +32,16 @@ pub async fn run(
     let options = DailyUnregisterPuzzleCommandOptions::from_options_list(&command.options);
 
     // Save data
-    let mut config = Config::get().expect("Failed to load config");
-    let removed_puzzle_config = config.daily_puzzle_configs.remove(&options.channel.id);
+    let removed_puzzle_config = bot
+        .storage
+        .remove_daily_puzzle_config(options.channel.id)
+        .expect("Failed to save config");
 
     // Respond
     interaction
         .create_interaction_response(&ctx.http, |response| {
             response.interaction_response_data(|message| {
-                if removed_puzzle_config.is_some() {
+                if removed_puzzle_config {
                     message.ephemeral(true).add_embed(make_message_embed(
                         ResponseReason::Success,
                         &format!("Successfully removed the daily puzzle from <#{}>", options.channel.id),
@@ -14,12 +14,14 @@ pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteract
                         .title("🛟  Help")
                         .description(
                             "This is a Discord bot for posting leaderboards and puzzles from [Advent of Code](https://adventofcode.com/).\n\n\
-                            To get started, use the `/register` command in a server to set up your session token and leaderboard id. You can \
+                            To get started, use the `/register` command in a server to set up your leaderboard id and a session token or view key. You can \
                             find your leaderboard id by visiting the private leaderboard you want to use and checking the number at the end of the url.\n\n\
                             For example, if your leaderboard is at the following url: `https://adventofcode.com/2022/leaderboard/private/view/1234567`, \
                             then your leaderboard id is `1234567`.\n\n\
                             To get the session token, you'll need to check the network tab of developer tools while visiting the AoC website to find the \
                             `session` cookie in the request headers.\n\n\
+                            If you'd rather not share your session token, the owner of the leaderboard can instead give you its read-only view key, \
+                            which is shown on the leaderboard page.\n\n\
                             For more detailed info, including descriptions on all the commands, check out the [Github repository](https://github.com/giraugh/advent-of-code-friend#readme)."
                         )
                         .color(EMBED_COLOR)
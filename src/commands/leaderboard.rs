@@ -1,8 +1,13 @@
 use crate::bot::Bot;
+use crate::calendar::{EventCalendar, FIRST_YEAR, MAX_PUZZLE_COUNT};
 use crate::config::LeaderboardOrdering;
-use crate::format::{make_leaderboard_embed, make_message_embed, ResponseReason};
+use crate::format::{
+    make_day_leaderboard_embed, make_leaderboard_embed, make_message_embed,
+    make_score_verification_embed, ResponseReason,
+};
+use crate::links::linked_member_names;
 
-use chrono::{Datelike, Utc};
+use chrono::Utc;
 use serenity::builder::CreateApplicationCommand;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
@@ -10,13 +15,19 @@ use serenity::model::prelude::interaction::application_command::{
 };
 use serenity::prelude::Context;
 
-use super::{extract_int_option, extract_string_option, CommandOptions};
+use super::{
+    add_board_option, add_ordering_choices, extract_bool_option, extract_int_option,
+    extract_string_option, CommandOptions,
+};
 
 // Options //
 
 struct LeaderboardCommandOptions {
     ordering: LeaderboardOrdering,
     year: i32,
+    day: Option<usize>,
+    verify: bool,
+    board: Option<String>,
 }
 
 impl CommandOptions for LeaderboardCommandOptions {
@@ -27,7 +38,10 @@ impl CommandOptions for LeaderboardCommandOptions {
                 .unwrap_or(LeaderboardOrdering::LocalScore),
             year: extract_int_option(options_list, "year")
                 .map(|v| v as i32)
-                .unwrap_or_else(|| Utc::now().year()),
+                .unwrap_or_else(|| EventCalendar::current(Utc::now()).year()),
+            day: extract_int_option(options_list, "day").map(|v| v as usize),
+            verify: extract_bool_option(options_list, "verify").unwrap_or(false),
+            board: extract_string_option(options_list, "board"),
         }
     }
 }
@@ -38,14 +52,46 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
     // Parse command options
     let options = LeaderboardCommandOptions::from_options_list(&command.data.options);
 
-    if options.year > Utc::now().year() {
+    // Solve times can only be shown for days that have unlocked
+    let now = Utc::now();
+    let calendar = EventCalendar::new(options.year);
+    let locked_day = options.day.filter(|day| {
+        calendar
+            .unlock_time(*day as u32)
+            .is_none_or(|unlock| unlock > now)
+    });
+
+    let guild_id = command.guild_id.expect("command to have guild id");
+    let error = if options.year > EventCalendar::current(now).year() {
+        Some("You can't use a year in the future 🗞️".to_owned())
+    } else {
+        locked_day.map(|day| match calendar.unlock_time(day as u32) {
+            Some(unlock) => {
+                let timezone = bot
+                    .storage
+                    .guild_timezone_or_default(guild_id)
+                    .expect("Failed to load config");
+                format!(
+                    "Day {day} hasn't unlocked yet, it unlocks <t:{}:R> ({}) ⏳",
+                    unlock.timestamp(),
+                    unlock.with_timezone(&timezone).format("%b %-d %H:%M %Z")
+                )
+            }
+            None => format!(
+                "The {} event only has {} puzzles",
+                options.year,
+                calendar.puzzle_count()
+            ),
+        })
+    };
+
+    if let Some(error_str) = error {
         command
             .create_interaction_response(&ctx.http, |response| {
                 response.interaction_response_data(|message| {
-                    message.ephemeral(true).add_embed(make_message_embed(
-                        ResponseReason::Error,
-                        "You can't use a year in the future 🗞️",
-                    ))
+                    message
+                        .ephemeral(true)
+                        .add_embed(make_message_embed(ResponseReason::Error, &error_str))
                 })
             })
             .await
@@ -53,12 +99,14 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
         return;
     }
 
-    // Defer response
+    // Defer response, in case the leaderboard isn't cached (e.g. for a past year)
     command.defer(&ctx.http).await.unwrap();
 
     // Get leaderboard
-    let guild_id = command.guild_id.expect("command to have guild id");
-    let leaderboard = bot.get_registered_leaderboard(guild_id, options.year).await;
+    let leaderboard = bot
+        .get_registered_leaderboard(guild_id, options.board.as_deref(), options.year)
+        .await;
+    let names = linked_member_names(ctx, &*bot.storage, guild_id).await;
 
     // Respond
     match leaderboard {
@@ -67,7 +115,13 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
             // Respond
             command
                 .create_followup_message(&ctx.http, |message| {
-                    message.add_embed(make_leaderboard_embed(leaderboard, options.ordering))
+                    if options.verify {
+                        message.add_embed(make_score_verification_embed(&leaderboard.leaderboard));
+                    }
+                    message.add_embed(match options.day {
+                        Some(day) => make_day_leaderboard_embed(leaderboard, day, &names),
+                        None => make_leaderboard_embed(leaderboard, options.ordering, &names),
+                    })
                 })
                 .await
                 .expect("failed to create interaction response");
@@ -79,7 +133,7 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
                 .create_followup_message(&ctx.http, |message| {
                     message.add_embed(make_message_embed(
                         ResponseReason::Error,
-                        &format!("Failed to get leaderboard: {}\n\nYour session token may have expired. Please try rerunning `/register` with a fresh token.", error),
+                        &format!("Failed to get leaderboard. {error}"),
                     ))
                 })
                 .await
@@ -98,16 +152,31 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                 .description(
                     "Method used to order people in the leaderboard (default: local-score)",
                 )
-                .kind(CommandOptionType::String)
-                .add_string_choice("local-score", LeaderboardOrdering::LocalScore)
-                .add_string_choice("global-score", LeaderboardOrdering::GlobalScore)
-                .add_string_choice("stars", LeaderboardOrdering::Stars)
+                .kind(CommandOptionType::String);
+            add_ordering_choices(option)
         })
         .create_option(|option| {
             option
                 .name("year")
-                .description("Year to fetch leaderboard for (default: current year)")
+                .description("Year to fetch leaderboard for (default: the latest event)")
                 .kind(CommandOptionType::Integer)
-                .min_int_value(2015)
+                .min_int_value(FIRST_YEAR)
+        })
+        .create_option(|option| {
+            option
+                .name("day")
+                .description("Rank members by how quickly they solved this day instead")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(1)
+                .max_int_value(MAX_PUZZLE_COUNT)
+        })
+        .create_option(|option| {
+            option
+                .name("verify")
+                .description(
+                    "Also check the local scores from AoC against ones computed from star times",
+                )
+                .kind(CommandOptionType::Boolean)
         })
+        .create_option(add_board_option)
 }
@@ -0,0 +1,211 @@
+use crate::aoc::Leaderboard;
+use crate::bot::Bot;
+use crate::calendar::EventCalendar;
+use crate::format::{make_message_embed, member_name, ResponseReason};
+use crate::links::can_link;
+use crate::rewards::remove_reward_roles;
+use chrono::Utc;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::GuildId;
+use serenity::model::user::User;
+use serenity::prelude::Context;
+
+use super::{extract_string_option, extract_user_option, is_admin, CommandOptions};
+
+struct LinkCommandOptions {
+    member: String,
+    user: Option<User>,
+}
+
+impl CommandOptions for LinkCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            member: extract_string_option(options_list, "member").expect("Didn't find member"),
+            user: extract_user_option(options_list, "user"),
+        }
+    }
+}
+
+/// Find a member of a leaderboard by their ID (from autocomplete) or their name
+pub fn find_member<'a>(
+    leaderboard: &'a Leaderboard,
+    query: &str,
+) -> Option<&'a crate::aoc::LeaderboardMember> {
+    let query = query.trim();
+    leaderboard
+        .members
+        .values()
+        .find(|member| member.id.to_string() == query)
+        .or_else(|| {
+            leaderboard
+                .members
+                .values()
+                .find(|member| member_name(member).eq_ignore_ascii_case(query))
+        })
+}
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Parse options
+    let options = LinkCommandOptions::from_options_list(&command.data.options);
+    let guild_id = command.guild_id.expect("Expected guild ID");
+
+    // Defer response, getting the leaderboard and linked users can take a while
+    command.defer_ephemeral(&ctx.http).await.unwrap();
+
+    // Only admins can link someone other than themselves
+    let user_id = match &options.user {
+        Some(user) if user.id != command.user.id && !is_admin(command) => {
+            respond(
+                ctx,
+                command,
+                ResponseReason::Error,
+                "Only members with the Manage Server permission can link other users.",
+            )
+            .await;
+            return;
+        }
+        Some(user) => user.id,
+        None => command.user.id,
+    };
+
+    // Find the member on the leaderboard
+    let leaderboard = match bot
+        .get_registered_leaderboard(guild_id, None, EventCalendar::current(Utc::now()).year())
+        .await
+    {
+        Ok(leaderboard) => leaderboard,
+        Err(error) => {
+            respond(
+                ctx,
+                command,
+                ResponseReason::Error,
+                &format!("Failed to get leaderboard. {error}"),
+            )
+            .await;
+            return;
+        }
+    };
+    let Some(member) = find_member(&leaderboard.leaderboard, &options.member) else {
+        respond(
+            ctx,
+            command,
+            ResponseReason::Error,
+            "Couldn't find that member on this server's leaderboard. Pick one of the suggestions while typing.",
+        )
+        .await;
+        return;
+    };
+
+    // Don't let members be taken from whoever they're already linked to
+    let current = match bot.storage.member_links(guild_id) {
+        Ok(links) => links.get(&member.id).copied(),
+        Err(error) => {
+            log::error!("Failed to load member links for {guild_id}: {error}");
+            respond(
+                ctx,
+                command,
+                ResponseReason::Error,
+                "Something went wrong while checking existing links, please try again.",
+            )
+            .await;
+            return;
+        }
+    };
+    if !can_link(current, user_id, is_admin(command)) {
+        respond(
+            ctx,
+            command,
+            ResponseReason::Error,
+            &format!(
+                "**{}** is already linked to someone else. Ask them to `/unlink` first, or ask someone with the Manage Server permission to change it.",
+                member_name(member)
+            ),
+        )
+        .await;
+        return;
+    }
+
+    // Save data, each user can only be linked to one member
+    bot.storage
+        .link_member(guild_id, member.id, user_id)
+        .expect("Failed to save config");
+
+    // Whoever the member was taken from no longer has their reward roles
+    if let Some(previous) = current.filter(|previous| *previous != user_id) {
+        remove_reward_roles(ctx, &*bot.storage, guild_id, previous).await;
+    }
+
+    // Respond
+    respond(
+        ctx,
+        command,
+        ResponseReason::Success,
+        &format!(
+            "Linked **{}** to <@{}>. They will now be shown by their display name in leaderboards.\n\nUse `/unlink` to remove this link.",
+            member_name(member),
+            user_id
+        ),
+    )
+    .await;
+}
+
+async fn respond(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    reason: ResponseReason,
+    text: &str,
+) {
+    command
+        .create_followup_message(&ctx.http, |message| {
+            message
+                .ephemeral(true)
+                .add_embed(make_message_embed(reason, text))
+        })
+        .await
+        .expect("to respond to command");
+}
+
+/// Members of the default leaderboard whose names match what's been typed so far
+pub async fn member_choices(bot: &Bot, guild_id: GuildId, query: &str) -> Vec<(String, String)> {
+    match bot
+        .get_registered_leaderboard(guild_id, None, EventCalendar::current(Utc::now()).year())
+        .await
+    {
+        Ok(leaderboard) => {
+            let mut choices: Vec<_> = leaderboard
+                .leaderboard
+                .members
+                .values()
+                .map(|member| (member_name(member), member.id.to_string()))
+                .filter(|(name, _)| name.to_lowercase().contains(query))
+                .collect();
+            choices.sort();
+            choices
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("link")
+        .description("Link a member of the registered leaderboard to your discord account")
+        .create_option(|option| {
+            option
+                .name("member")
+                .description("Your name on the AoC leaderboard")
+                .kind(CommandOptionType::String)
+                .set_autocomplete(true)
+                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("user")
+                .description("Link someone else instead (requires the Manage Server permission)")
+                .kind(CommandOptionType::User)
+        })
+}
@@ -1,15 +1,35 @@
-use serenity::model::prelude::{
-    command::CommandOptionType,
-    interaction::application_command::{CommandDataOption, CommandDataOptionValue},
-    PartialChannel,
+use chrono_tz::Tz;
+use serenity::{
+    builder::CreateApplicationCommandOption,
+    model::prelude::{
+        command::CommandOptionType,
+        interaction::application_command::{
+            ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+        },
+        interaction::autocomplete::AutocompleteInteraction,
+        GuildId, PartialChannel, Role,
+    },
+    model::user::User,
+    prelude::Context,
 };
+use strum::IntoEnumIterator;
 
+use crate::{bot::Bot, config::LeaderboardOrdering, schedule::Schedule};
+
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+pub mod changes;
 pub mod daily;
 pub mod help;
 pub mod leaderboard;
+pub mod link;
+pub mod notify;
 pub mod puzzle;
 pub mod register;
+pub mod reward;
 pub mod status;
+pub mod timezone;
+pub mod unlink;
 pub mod unregister;
 
 fn extract_string_option(options_list: &[CommandDataOption], option_name: &str) -> Option<String> {
@@ -39,6 +59,50 @@ fn extract_channel_option(
     })
 }
 
+fn extract_bool_option(options_list: &[CommandDataOption], option_name: &str) -> Option<bool> {
+    let option = options_list.iter().find(|opt| opt.name == option_name)?;
+    option.resolved.clone().map(|v| match v {
+        CommandDataOptionValue::Boolean(v) => v,
+        _ => panic!("Expected boolean option"),
+    })
+}
+
+fn extract_user_option(options_list: &[CommandDataOption], option_name: &str) -> Option<User> {
+    let option = options_list.iter().find(|opt| opt.name == option_name)?;
+    option.resolved.clone().map(|v| match v {
+        CommandDataOptionValue::User(v, _) => v,
+        _ => panic!("Expected user option"),
+    })
+}
+
+fn extract_role_option(options_list: &[CommandDataOption], option_name: &str) -> Option<Role> {
+    let option = options_list.iter().find(|opt| opt.name == option_name)?;
+    option.resolved.clone().map(|v| match v {
+        CommandDataOptionValue::Role(v) => v,
+        _ => panic!("Expected role option"),
+    })
+}
+
+/// Get the option currently being autocompleted, which may be part of a subcommand
+fn extract_focused_option(options_list: &[CommandDataOption]) -> Option<&CommandDataOption> {
+    options_list.iter().find_map(|opt| {
+        if opt.focused {
+            Some(opt)
+        } else {
+            extract_focused_option(&opt.options)
+        }
+    })
+}
+
+/// Whether the user running a command can manage the server
+fn is_admin(command: &ApplicationCommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild())
+}
+
 fn extract_subcommand(options_list: &[CommandDataOption]) -> Option<&CommandDataOption> {
     let option = options_list.iter().find(|opt| {
         opt.kind == CommandOptionType::SubCommand || opt.kind == CommandOptionType::SubCommandGroup
@@ -46,6 +110,107 @@ fn extract_subcommand(options_list: &[CommandDataOption]) -> Option<&CommandData
     Some(option)
 }
 
+/// Suggest values for whichever option is being typed
+pub async fn autocomplete(bot: &Bot, ctx: &Context, interaction: &AutocompleteInteraction) {
+    let Some(guild_id) = interaction.guild_id else {
+        return;
+    };
+    let Some(option) = extract_focused_option(&interaction.data.options) else {
+        return;
+    };
+    let query = option
+        .value
+        .as_ref()
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    // Get choices as (name, value) pairs
+    let mut choices = match option.name.as_str() {
+        "member" => link::member_choices(bot, guild_id, &query).await,
+        "board" => board_choices(bot, guild_id, &query),
+        "timezone" => timezone::timezone_choices(&query),
+        _ => Vec::new(),
+    };
+    choices.truncate(MAX_AUTOCOMPLETE_CHOICES);
+
+    let result = interaction
+        .create_autocomplete_response(&ctx.http, |response| {
+            for (name, value) in choices {
+                response.add_string_choice(name, value);
+            }
+            response
+        })
+        .await;
+    if let Err(error) = result {
+        log::warn!("Failed to respond to autocomplete: {error}");
+    }
+}
+
+/// Names of a guild's registered leaderboards that match what's been typed so far
+fn board_choices(bot: &Bot, guild_id: GuildId, query: &str) -> Vec<(String, String)> {
+    bot.storage
+        .guild_configs(guild_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|config| config.name.to_lowercase().contains(query))
+        .map(|config| match config.is_default {
+            true => (format!("{} (default)", config.name), config.name),
+            false => (config.name.clone(), config.name),
+        })
+        .collect()
+}
+
+/// Add an option to pick one of the guild's registered leaderboards
+fn add_board_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    option
+        .name("board")
+        .description("Which registered leaderboard to use (defaults to the server's default)")
+        .kind(CommandOptionType::String)
+        .set_autocomplete(true)
+}
+
+/// Add an option to post on a schedule instead of once a day
+fn add_schedule_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    option
+        .name("schedule")
+        .description("When to post, 30+ min apart, as `minute hour day month weekday` (replaces hour), e.g. `0 9 * 12 *`")
+        .kind(CommandOptionType::String)
+}
+
+/// When a daily is posted, e.g. "at **09:00 EST** every day"
+fn describe_daily_time(schedule: Option<&Schedule>, hour: usize, timezone: Tz) -> String {
+    match schedule {
+        Some(schedule) => format!("on the schedule `{schedule}` ({})", timezone.name()),
+        None => format!("at **{hour:0>2}:00 {}** every day", timezone.name()),
+    }
+}
+
 trait CommandOptions {
     fn from_options_list(options_list: &[CommandDataOption]) -> Self;
 }
+
+/// Add every leaderboard ordering as a choice for a string option
+fn add_ordering_choices(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    for ordering in LeaderboardOrdering::iter() {
+        let name = match ordering {
+            LeaderboardOrdering::LocalScore => "local-score",
+            LeaderboardOrdering::GlobalScore => "global-score",
+            LeaderboardOrdering::Stars => "stars",
+            LeaderboardOrdering::ComputedScore => "computed-score",
+            LeaderboardOrdering::StarsThenLastStar => "stars-then-last-star",
+            LeaderboardOrdering::MedianSolveTime => "median-solve-time",
+            LeaderboardOrdering::TotalSolveTime => "total-solve-time",
+            LeaderboardOrdering::AveragePartDelta => "average-part-delta",
+            LeaderboardOrdering::CurrentStreak => "current-streak",
+        };
+        option.add_string_choice(name, ordering);
+    }
+    option
+}
@@ -0,0 +1,31 @@
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::prelude::interaction::application_command::ApplicationCommandInteraction,
+    prelude::Context,
+};
+
+use crate::bot::Bot;
+
+use super::extract_subcommand;
+
+mod stars;
+mod unregister;
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    match extract_subcommand(&command.data.options) {
+        Some(subcommand) => match subcommand.name.as_str() {
+            "stars" => stars::run(bot, ctx, command, subcommand).await,
+            "unregister" => unregister::run(bot, ctx, command, subcommand).await,
+            _ => panic!("Unknown subcommand"),
+        },
+        None => panic!("Command group called without subcommand"),
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("notify")
+        .description("Post live updates from the leaderboard to a specific channel")
+        .add_option(stars::register())
+        .add_option(unregister::register())
+}
@@ -0,0 +1,82 @@
+use crate::bot::Bot;
+use crate::config::StarNotifyConfig;
+use crate::format::{make_message_embed, ResponseReason};
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::PartialChannel;
+use serenity::prelude::Context;
+
+use super::super::{
+    add_board_option, extract_channel_option, extract_string_option, CommandOptions,
+};
+
+struct NotifyStarsCommandOptions {
+    channel: PartialChannel,
+    board: Option<String>,
+}
+
+impl CommandOptions for NotifyStarsCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            channel: extract_channel_option(options_list, "channel").expect("Didn't find channel"),
+            board: extract_string_option(options_list, "board"),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = NotifyStarsCommandOptions::from_options_list(&command.options);
+
+    // Save data
+    bot.storage
+        .set_star_notify_config(
+            options.channel.id,
+            &StarNotifyConfig {
+                guild_id: interaction.guild_id.expect("guild id"),
+                board: options.board,
+            },
+        )
+        .expect("Failed to save config");
+
+    // Respond
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|message| {
+                message.ephemeral(true).add_embed(make_message_embed(
+                    ResponseReason::Success,
+                    &format!(
+                        "Successfully registered star notifications to <#{}>. New stars will be posted there as they are earned during December (the leaderboard is checked every 15 minutes).\n\n\
+                        Use `/notify unregister` to stop them.",
+                        options.channel.id,
+                    ),
+                ))
+            })
+        })
+        .await
+        .expect("to respond to command");
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("stars")
+        .description("Post a message in a specific channel whenever someone earns a star")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("channel")
+                .description("Where you want the notifications to be sent")
+                .kind(CommandOptionType::Channel)
+                .required(true)
+        })
+        .create_sub_option(add_board_option)
+        .to_owned()
+}
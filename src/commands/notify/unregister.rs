@@ -0,0 +1,74 @@
+use crate::bot::Bot;
+use crate::format::{make_message_embed, ResponseReason};
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::PartialChannel;
+use serenity::prelude::Context;
+
+use super::super::{extract_channel_option, CommandOptions};
+
+struct NotifyUnregisterCommandOptions {
+    channel: PartialChannel,
+}
+
+impl CommandOptions for NotifyUnregisterCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            channel: extract_channel_option(options_list, "channel").expect("Didn't find channel"),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = NotifyUnregisterCommandOptions::from_options_list(&command.options);
+
+    // Save data
+    let removed_notify_config = bot
+        .storage
+        .remove_star_notify_config(options.channel.id)
+        .expect("Failed to save config");
+
+    // Respond
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|message| {
+                if removed_notify_config {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Success,
+                        &format!("Successfully removed star notifications from <#{}>", options.channel.id),
+                    ))
+                } else {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Error,
+                        "There were no star notifications on that channel. You can set them up with `/notify stars`.",
+                    ))
+                }
+            })
+        })
+        .await
+        .expect("to respond to command");
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("unregister")
+        .description("Stop posting star notifications in a specific channel")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("channel")
+                .description("Where the notifications are registered")
+                .kind(CommandOptionType::Channel)
+                .required(true)
+        })
+        .to_owned()
+}
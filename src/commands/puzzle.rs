@@ -1,4 +1,4 @@
-use chrono::{Datelike, FixedOffset, Utc};
+use chrono::Utc;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
 use serenity::prelude::Context;
@@ -8,23 +8,21 @@ use serenity::{
 };
 
 use crate::bot::Bot;
-use crate::daily::EST_SECS;
+use crate::calendar::{EventCalendar, FIRST_YEAR, MAX_PUZZLE_COUNT};
 use crate::format::{make_message_embed, make_puzzle_embed, ResponseReason};
 
 use super::{extract_int_option, CommandOptions};
 
 struct PuzzleCommandOptions {
-    day: Option<usize>,
-    year: usize,
+    day: Option<u32>,
+    year: Option<i32>,
 }
 
 impl CommandOptions for PuzzleCommandOptions {
     fn from_options_list(options_list: &[CommandDataOption]) -> Self {
         Self {
-            day: extract_int_option(options_list, "day").map(|v| v as usize),
-            year: extract_int_option(options_list, "year")
-                .map(|v| v as usize)
-                .unwrap_or_else(|| Utc::now().year() as usize),
+            day: extract_int_option(options_list, "day").map(|v| v as u32),
+            year: extract_int_option(options_list, "year").map(|v| v as i32),
         }
     }
 }
@@ -34,23 +32,36 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
     // Parse options
     let options = PuzzleCommandOptions::from_options_list(&command.data.options);
 
-    // Get current year
-    let tz = FixedOffset::east_opt(EST_SECS).unwrap();
-    let time = Utc::now().with_timezone(&tz);
-    let year = time.year() as usize;
-    let day = time.day() as usize;
+    // Get the latest event, which is last year's until December
+    let now = Utc::now();
+    let current = EventCalendar::current(now);
+    let calendar = EventCalendar::new(options.year.unwrap_or(current.year()));
+    let day = options
+        .day
+        .unwrap_or_else(|| calendar.latest_unlocked_day(now));
 
-    let error = if Utc::now().month() != 12 && options.year == year {
+    let error = if calendar.year() > current.year() {
         Some(format!(
-            "It's not yet December, please specify a year between 2015 and {}",
-            year - 1,
+            "The {} event hasn't started yet, please specify a year between {FIRST_YEAR} and {}",
+            calendar.year(),
+            current.year(),
         ))
-    } else if options.year > year {
-        Some("You can't use a year in the future 🗞️".to_owned())
-    } else if options.day.is_none() && options.year != year {
+    } else if options.day.is_none() && calendar != current {
         Some("When using a previous year, you must also specify a day".to_owned())
+    } else if day > calendar.puzzle_count() {
+        Some(format!(
+            "The {} event only has {} puzzles",
+            calendar.year(),
+            calendar.puzzle_count()
+        ))
     } else {
-        None
+        match calendar.unlock_time(day) {
+            Some(unlock) if unlock > now => Some(format!(
+                "Day {day} hasn't unlocked yet, it unlocks <t:{}:R> ⏳",
+                unlock.timestamp()
+            )),
+            _ => None,
+        }
     };
 
     if let Some(error_str) = error {
@@ -68,19 +79,17 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
         // Defer response
         command.defer(&ctx.http).await.unwrap();
 
-        let puzzle_details = {
-            let mut aoc_data = bot.aoc_data.lock().await;
-            aoc_data
-                .get_puzzle_details(options.year, options.day.unwrap_or(day))
-                .await
-        }
-        .ok();
+        let puzzle_details = bot
+            .aoc_data
+            .get_puzzle_details(calendar.year() as usize, day as usize)
+            .await
+            .ok();
 
         command
             .create_followup_message(&ctx.http, |message| {
                 message.add_embed(make_puzzle_embed(
-                    options.year,
-                    options.day.unwrap_or(day),
+                    calendar.year() as usize,
+                    day as usize,
                     puzzle_details,
                     false,
                 ))
@@ -100,13 +109,13 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                 .description("Day of December to fetch puzzle for (defaults to latest day)")
                 .kind(CommandOptionType::Integer)
                 .min_int_value(1)
-                .max_int_value(25)
+                .max_int_value(MAX_PUZZLE_COUNT)
         })
         .create_option(|option| {
             option
                 .name("year")
-                .description("Year to fetch puzzle for (default: current year)")
+                .description("Year to fetch puzzle for (default: the latest event)")
                 .kind(CommandOptionType::Integer)
-                .min_int_value(2015)
+                .min_int_value(FIRST_YEAR)
         })
 }
@@ -1,7 +1,9 @@
+use crate::aoc::{LeaderboardAuth, COMBINED_ID_SEPARATOR};
 use crate::bot::Bot;
-use crate::config::{Config, GuildConfig};
+use crate::calendar::EventCalendar;
+use crate::config::{GuildConfig, DEFAULT_BOARD_NAME};
 use crate::format::{make_message_embed, ResponseReason};
-use chrono::{Datelike, Utc};
+use chrono::Utc;
 use serenity::builder::CreateApplicationCommand;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
@@ -9,20 +11,50 @@ use serenity::model::prelude::interaction::application_command::{
 };
 use serenity::prelude::Context;
 
-use super::{extract_string_option, CommandOptions};
+use super::{extract_bool_option, extract_string_option, CommandOptions};
 
 struct RegisterCommandOptions {
-    session_token: String,
-    leaderboard_id: String,
+    leaderboard_ids: Vec<String>,
+    session_token: Option<String>,
+    view_key: Option<String>,
+    name: String,
+    default: Option<bool>,
 }
 
 impl CommandOptions for RegisterCommandOptions {
     fn from_options_list(options_list: &[CommandDataOption]) -> Self {
         Self {
-            session_token: extract_string_option(options_list, "session_token")
-                .expect("Didn't find session token"),
-            leaderboard_id: extract_string_option(options_list, "leaderboard_id")
-                .expect("Didn't find leaderboard id"),
+            leaderboard_ids: extract_string_option(options_list, "leaderboard_id")
+                .expect("Didn't find leaderboard id")
+                .split(COMBINED_ID_SEPARATOR)
+                .map(|id| id.trim().to_owned())
+                .filter(|id| !id.is_empty())
+                .collect(),
+            session_token: extract_string_option(options_list, "session_token"),
+            view_key: extract_string_option(options_list, "view_key"),
+            name: extract_string_option(options_list, "name")
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| DEFAULT_BOARD_NAME.to_owned()),
+            default: extract_bool_option(options_list, "default"),
+        }
+    }
+}
+
+impl RegisterCommandOptions {
+    /// Exactly one of the session token and view key must be provided, and a view key only
+    /// gives access to the one leaderboard it belongs to
+    fn auth(&self) -> Result<LeaderboardAuth, &'static str> {
+        if self.leaderboard_ids.is_empty() {
+            return Err("Please provide a `leaderboard_id`.");
+        }
+        match (&self.session_token, &self.view_key) {
+            (Some(token), None) => Ok(LeaderboardAuth::SessionToken(token.trim().to_owned())),
+            (None, Some(_)) if self.leaderboard_ids.len() > 1 => Err(
+                "A `view_key` only works for one leaderboard. To combine several, use a `session_token` from an account that's a member of all of them.",
+            ),
+            (None, Some(key)) => Ok(LeaderboardAuth::ViewKey(key.trim().to_owned())),
+            _ => Err("Please provide either a `session_token` or a `view_key` (but not both)."),
         }
     }
 }
@@ -30,43 +62,80 @@ impl CommandOptions for RegisterCommandOptions {
 pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
     // Parse options
     let options = RegisterCommandOptions::from_options_list(&command.data.options);
-
-    let mut config = Config::get().expect("Failed to load config");
-
-    // Try fetching leaderboard and fail if it doesn't exist
-    let can_fetch_leaderboard = {
-        let mut aoc_data = bot.aoc_data.lock().await;
-        aoc_data
-            .get_leaderboard(
-                &Utc::now().year().to_string(),
-                &options.leaderboard_id,
-                &options.session_token,
-                true,
-            )
-            .await
-            .is_ok()
+    let auth = match options.auth() {
+        Ok(auth) => auth,
+        Err(error) => {
+            command
+                .create_interaction_response(&ctx.http, |response| {
+                    response.interaction_response_data(|message| {
+                        message
+                            .ephemeral(true)
+                            .add_embed(make_message_embed(ResponseReason::Error, error))
+                    })
+                })
+                .await
+                .expect("to respond to command");
+            return;
+        }
     };
+    let leaderboard_ids: Vec<&str> = options.leaderboard_ids.iter().map(String::as_str).collect();
+    let guild_id = command.guild_id.expect("Expected guild ID");
+    let existing = bot
+        .storage
+        .guild_config(guild_id, Some(&options.name))
+        .expect("Failed to load config");
 
-    if !can_fetch_leaderboard {
-        command.create_interaction_response(&ctx.http, |response| {
-            response.interaction_response_data(|message| {
-                message.ephemeral(true).add_embed(make_message_embed(
-                    ResponseReason::Error,
-                    "Something went wrong while trying to fetch that leaderboard. Please check your session token and leaderboard ID are correct and try again.",
-                ))
+    // Try fetching leaderboard and fail if it doesn't exist. New details have to be checked
+    // with AoC, but the cache will do if nothing has changed.
+    let unchanged = existing.as_ref().is_some_and(|existing| {
+        existing.auth == auth && existing.leaderboard_ids() == leaderboard_ids
+    });
+    let fetch_result = bot
+        .aoc_data
+        .get_combined_leaderboard(
+            &EventCalendar::current(Utc::now()).year().to_string(),
+            &leaderboard_ids,
+            &auth,
+            !unchanged,
+        )
+        .await;
+
+    if let Err(error) = fetch_result {
+        log::warn!("Failed to register leaderboard: {error}");
+        command
+            .create_interaction_response(&ctx.http, |response| {
+                response.interaction_response_data(|message| {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Error,
+                        &format!(
+                            "Something went wrong while trying to fetch that leaderboard. {}",
+                            error.user_message()
+                        ),
+                    ))
+                })
             })
-        }).await.expect("to repond to command");
+            .await
+            .expect("to repond to command");
         return;
     }
 
+    // Replacing a board keeps it as the default unless told otherwise
+    let is_default = options
+        .default
+        .unwrap_or_else(|| existing.is_some_and(|existing| existing.is_default));
+
     // Save data
-    config.guild_configs.insert(
-        command.guild_id.expect("Expected guild ID"),
-        GuildConfig {
-            session_token: options.session_token,
-            leaderboard_id: options.leaderboard_id,
-        },
-    );
+    bot.storage
+        .set_guild_config(
+            guild_id,
+            &GuildConfig {
+                name: options.name.clone(),
+                is_default,
+                auth,
+                leaderboard_id: leaderboard_ids.join(&COMBINED_ID_SEPARATOR.to_string()),
+            },
+        )
+        .expect("Failed to save config");
 
     // Respond
     command
@@ -74,7 +143,11 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
             response.interaction_response_data(|message| {
                 message.ephemeral(true).add_embed(make_message_embed(
                     ResponseReason::Success,
-                    "Successfully registered your leaderboard to this server. You can now set up daily announcements and run `/leaderboard`.\n\nRun this command again to change the registration details, or use `/unregister` to remove this registration.",
+                    &format!(
+                        "Successfully registered your leaderboard to this server as `{}`. You can now set up daily announcements and run `/leaderboard`.\n\n\
+                        Run this command again with the same name to change the registration details, or use `/unregister` to remove this registration.",
+                        options.name,
+                    ),
                 ))
             })
         })
@@ -85,19 +158,36 @@ pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteracti
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
     command
         .name("register")
-        .description("Bind a session token and leaderboard ID to this server")
+        .description("Bind a leaderboard ID and a session token or view key to this server")
+        .create_option(|option| {
+            option
+                .name("leaderboard_id")
+                .description("Found at the end of a private leaderboard URL, separate several with commas to combine them")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
         .create_option(|option| {
             option
                 .name("session_token")
                 .description("Your AoC session cookie (valid for approx. 30 days)")
                 .kind(CommandOptionType::String)
-                .required(true)
         })
         .create_option(|option| {
             option
-                .name("leaderboard_id")
-                .description("Can be found at the end of a private leaderboard page URL")
+                .name("view_key")
+                .description("A read-only view key shared by the leaderboard owner, instead of a session token")
                 .kind(CommandOptionType::String)
-                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("name")
+                .description("A name for this leaderboard, if the server has more than one (default: default)")
+                .kind(CommandOptionType::String)
+        })
+        .create_option(|option| {
+            option
+                .name("default")
+                .description("Use this leaderboard when a command doesn't pick one")
+                .kind(CommandOptionType::Boolean)
         })
 }
@@ -0,0 +1,58 @@
+use crate::bot::Bot;
+use crate::config::RewardRequirement;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::Role;
+use serenity::prelude::Context;
+
+use super::super::{extract_role_option, CommandOptions};
+use super::save_reward;
+
+struct RewardCompleteCommandOptions {
+    role: Role,
+}
+
+impl CommandOptions for RewardCompleteCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            role: extract_role_option(options_list, "role").expect("Didn't find role"),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = RewardCompleteCommandOptions::from_options_list(&command.options);
+
+    save_reward(
+        bot,
+        ctx,
+        interaction,
+        &options.role,
+        RewardRequirement::AllStars,
+    )
+    .await;
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("complete")
+        .description("Give a role to members who have earned every star of the event")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("role")
+                .description("The role to give")
+                .kind(CommandOptionType::Role)
+                .required(true)
+        })
+        .to_owned()
+}
@@ -0,0 +1,82 @@
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{
+        prelude::{interaction::application_command::ApplicationCommandInteraction, Role},
+        Permissions,
+    },
+    prelude::Context,
+};
+
+use crate::{
+    bot::Bot,
+    config::{RewardRequirement, RoleRewardConfig},
+    format::{make_message_embed, ResponseReason},
+};
+
+use super::extract_subcommand;
+
+mod complete;
+mod remove;
+mod stars;
+mod top;
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    match extract_subcommand(&command.data.options) {
+        Some(subcommand) => match subcommand.name.as_str() {
+            "stars" => stars::run(bot, ctx, command, subcommand).await,
+            "complete" => complete::run(bot, ctx, command, subcommand).await,
+            "top" => top::run(bot, ctx, command, subcommand).await,
+            "remove" => remove::run(bot, ctx, command, subcommand).await,
+            _ => panic!("Unknown subcommand"),
+        },
+        None => panic!("Command group called without subcommand"),
+    }
+}
+
+/// Save a role reward and let the user know when it will be given out
+async fn save_reward(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    role: &Role,
+    requirement: RewardRequirement,
+) {
+    // Save data
+    bot.storage
+        .set_role_reward_config(
+            role.id,
+            &RoleRewardConfig {
+                guild_id: interaction.guild_id.expect("guild id"),
+                requirement,
+            },
+        )
+        .expect("Failed to save config");
+
+    // Respond
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|message| {
+                message.ephemeral(true).add_embed(make_message_embed(
+                    ResponseReason::Success,
+                    &format!(
+                        "Successfully set up <@&{}> as a reward. It will be given to and taken from linked members each time the leaderboard is refreshed.\n\n\
+                        Members need to `/link` their discord account to receive rewards, and the bot needs permission to manage the role.",
+                        role.id,
+                    ),
+                ))
+            })
+        })
+        .await
+        .expect("to respond to command");
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("reward")
+        .description("Give roles to members of the leaderboard for their progress")
+        .default_member_permissions(Permissions::MANAGE_ROLES)
+        .add_option(stars::register())
+        .add_option(complete::register())
+        .add_option(top::register())
+        .add_option(remove::register())
+}
@@ -0,0 +1,77 @@
+use crate::bot::Bot;
+use crate::format::{make_message_embed, ResponseReason};
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::Role;
+use serenity::prelude::Context;
+
+use super::super::{extract_role_option, CommandOptions};
+
+struct RewardRemoveCommandOptions {
+    role: Role,
+}
+
+impl CommandOptions for RewardRemoveCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            role: extract_role_option(options_list, "role").expect("Didn't find role"),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = RewardRemoveCommandOptions::from_options_list(&command.options);
+
+    // Save data
+    let removed_reward_config = bot
+        .storage
+        .remove_role_reward_config(options.role.id)
+        .expect("Failed to save config");
+
+    // Respond
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|message| {
+                if removed_reward_config {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Success,
+                        &format!(
+                            "<@&{}> is no longer a reward. Members who already have it will keep it.",
+                            options.role.id
+                        ),
+                    ))
+                } else {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Error,
+                        "That role isn't a reward. You can set one up with `/reward stars`, `/reward complete` or `/reward top`.",
+                    ))
+                }
+            })
+        })
+        .await
+        .expect("to respond to command");
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("remove")
+        .description("Stop giving out a role as a reward")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("role")
+                .description("The reward role")
+                .kind(CommandOptionType::Role)
+                .required(true)
+        })
+        .to_owned()
+}
@@ -0,0 +1,69 @@
+use crate::bot::Bot;
+use crate::config::RewardRequirement;
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::Role;
+use serenity::prelude::Context;
+
+use super::super::{extract_int_option, extract_role_option, CommandOptions};
+use super::save_reward;
+
+struct RewardStarsCommandOptions {
+    role: Role,
+    stars: isize,
+}
+
+impl CommandOptions for RewardStarsCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            role: extract_role_option(options_list, "role").expect("Didn't find role"),
+            stars: extract_int_option(options_list, "stars").expect("Didn't find stars"),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = RewardStarsCommandOptions::from_options_list(&command.options);
+
+    save_reward(
+        bot,
+        ctx,
+        interaction,
+        &options.role,
+        RewardRequirement::Stars(options.stars as usize),
+    )
+    .await;
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("stars")
+        .description("Give a role to members with at least some number of stars")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("role")
+                .description("The role to give")
+                .kind(CommandOptionType::Role)
+                .required(true)
+        })
+        .create_sub_option(|option| {
+            option
+                .name("stars")
+                .description("How many stars are needed")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(1)
+                .max_int_value(50)
+                .required(true)
+        })
+        .to_owned()
+}
@@ -0,0 +1,86 @@
+use crate::bot::Bot;
+use crate::config::{LeaderboardOrdering, RewardRequirement};
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::Role;
+use serenity::prelude::Context;
+
+use super::super::{
+    add_ordering_choices, extract_int_option, extract_role_option, extract_string_option,
+    CommandOptions,
+};
+use super::save_reward;
+
+struct RewardTopCommandOptions {
+    role: Role,
+    rank: isize,
+    ordering: LeaderboardOrdering,
+}
+
+impl CommandOptions for RewardTopCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            role: extract_role_option(options_list, "role").expect("Didn't find role"),
+            rank: extract_int_option(options_list, "rank").expect("Didn't find rank"),
+            ordering: extract_string_option(options_list, "ordering")
+                .and_then(|ordering| ordering.parse().ok())
+                .unwrap_or(LeaderboardOrdering::LocalScore),
+        }
+    }
+}
+
+pub async fn run(
+    bot: &Bot,
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    command: &CommandDataOption,
+) {
+    // Parse options
+    let options = RewardTopCommandOptions::from_options_list(&command.options);
+
+    save_reward(
+        bot,
+        ctx,
+        interaction,
+        &options.role,
+        RewardRequirement::TopRank {
+            rank: options.rank as usize,
+            ordering: options.ordering,
+        },
+    )
+    .await;
+}
+
+pub fn register() -> CreateApplicationCommandOption {
+    CreateApplicationCommandOption::default()
+        .name("top")
+        .description("Give a role to the members at the top of the leaderboard")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+            option
+                .name("role")
+                .description("The role to give")
+                .kind(CommandOptionType::Role)
+                .required(true)
+        })
+        .create_sub_option(|option| {
+            option
+                .name("rank")
+                .description("How many places get the role, e.g. 3 for the top 3")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(1)
+                .required(true)
+        })
+        .create_sub_option(|option| {
+            add_ordering_choices(
+                option
+                    .name("ordering")
+                    .description("How to rank the leaderboard (defaults to local score)")
+                    .kind(CommandOptionType::String),
+            )
+        })
+        .to_owned()
+}
@@ -1,13 +1,19 @@
 use std::collections::HashMap;
 
+use crate::aoc::LeaderboardAuth;
 use crate::bot::Bot;
-use crate::config::{Config, DailyLeaderboardConfig, DailyPuzzleConfig};
+use crate::config::{
+    DailyLeaderboardConfig, DailyPuzzleConfig, RewardRequirement, RoleRewardConfig,
+    StarNotifyConfig,
+};
 use crate::format::EMBED_COLOR;
 use serenity::builder::CreateApplicationCommand;
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
-use serenity::model::prelude::ChannelId;
+use serenity::model::prelude::{ChannelId, RoleId};
 use serenity::prelude::Context;
 
+use super::describe_daily_time;
+
 trait NotEmptyOr {
     fn not_empty_or(self, or: &str) -> Self;
 }
@@ -22,22 +28,57 @@ impl NotEmptyOr for String {
     }
 }
 
-pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+/// A warning for a daily that was turned off after failing to post too many times
+fn disabled_note(is_disabled: bool) -> &'static str {
+    if is_disabled {
+        " ⚠️ turned off after failing to post, set it up again to turn it back on"
+    } else {
+        ""
+    }
+}
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
     let guild_id = command.guild_id.expect("guild id expected");
     // Get data
-    let config = Config::get().expect("Failed to load config");
+    let guild_configs = bot
+        .storage
+        .guild_configs(guild_id)
+        .expect("Failed to load config");
+
+    let timezone = bot
+        .storage
+        .guild_timezone_or_default(guild_id)
+        .expect("Failed to load config");
+
+    let daily_leaderboard_configs: HashMap<ChannelId, DailyLeaderboardConfig> = bot
+        .storage
+        .daily_leaderboard_configs()
+        .expect("Failed to load config")
+        .into_iter()
+        .filter(|config| config.1.guild_id == guild_id)
+        .collect();
 
-    let guild_config = config.guild_configs.get(&guild_id);
+    let daily_puzzle_configs: HashMap<ChannelId, DailyPuzzleConfig> = bot
+        .storage
+        .daily_puzzle_configs()
+        .expect("Failed to load config")
+        .into_iter()
+        .filter(|config| config.1.guild_id == guild_id)
+        .collect();
 
-    let daily_leaderboard_configs: HashMap<&ChannelId, &DailyLeaderboardConfig> = config
-        .daily_leaderboard_configs
-        .iter()
+    let star_notify_configs: HashMap<ChannelId, StarNotifyConfig> = bot
+        .storage
+        .star_notify_configs()
+        .expect("Failed to load config")
+        .into_iter()
         .filter(|config| config.1.guild_id == guild_id)
         .collect();
 
-    let daily_puzzle_configs: HashMap<&ChannelId, &DailyPuzzleConfig> = config
-        .daily_puzzle_configs
-        .iter()
+    let role_reward_configs: HashMap<RoleId, RoleRewardConfig> = bot
+        .storage
+        .role_reward_configs()
+        .expect("Failed to load config")
+        .into_iter()
         .filter(|config| config.1.guild_id == guild_id)
         .collect();
 
@@ -48,20 +89,59 @@ pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteract
                 message.ephemeral(true).embed(|embed| {
                     embed
                         .title("📋  Status")
-                        .description(if guild_config.is_some() {
-                            format!(
-                                "✅ This server has a registered leaderboard (`{}`)",
-                                guild_config.unwrap().leaderboard_id
-                            )
-                        } else {
-                            String::from("❌ This server does not have a registered leaderboard")
-                        })
+                        .description(
+                            guild_configs
+                                .iter()
+                                .map(|guild_config| {
+                                    format!(
+                                        "✅ `{}`{} is {} using a {}",
+                                        guild_config.name,
+                                        if guild_config.is_default {
+                                            " (default)"
+                                        } else {
+                                            ""
+                                        },
+                                        match guild_config.leaderboard_ids().as_slice() {
+                                            [leaderboard_id] => {
+                                                format!("leaderboard `{leaderboard_id}`")
+                                            }
+                                            leaderboard_ids => format!(
+                                                "leaderboards `{}` combined",
+                                                leaderboard_ids.join("`, `")
+                                            ),
+                                        },
+                                        match guild_config.auth {
+                                            LeaderboardAuth::SessionToken(_) => "session token",
+                                            LeaderboardAuth::ViewKey(_) => "view key",
+                                        }
+                                    )
+                                })
+                                .collect::<Vec<String>>()
+                                .join("\n")
+                                .not_empty_or(
+                                    "❌ This server does not have a registered leaderboard",
+                                ),
+                        )
+                        .field("Timezone", timezone.name(), false)
                         .field(
                             "Daily Leaderboards",
                             daily_leaderboard_configs
                                 .iter()
                                 .map(|config| {
-                                    format!("<#{}> at {:0>2}:00", config.0, config.1.hour)
+                                    format!(
+                                        "<#{}> {}{}{}",
+                                        config.0,
+                                        describe_daily_time(
+                                            config.1.schedule.as_ref(),
+                                            config.1.hour,
+                                            timezone
+                                        ),
+                                        match &config.1.board {
+                                            Some(board) => format!(" for `{board}`"),
+                                            None => String::new(),
+                                        },
+                                        disabled_note(config.1.is_disabled())
+                                    )
                                 })
                                 .collect::<Vec<String>>()
                                 .join("\n")
@@ -73,13 +153,67 @@ pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteract
                             daily_puzzle_configs
                                 .iter()
                                 .map(|config| {
-                                    format!("<#{}> at {:0>2}:00", config.0, config.1.hour)
+                                    format!(
+                                        "<#{}> {}{}",
+                                        config.0,
+                                        describe_daily_time(
+                                            config.1.schedule.as_ref(),
+                                            config.1.hour,
+                                            timezone
+                                        ),
+                                        disabled_note(config.1.is_disabled())
+                                    )
                                 })
                                 .collect::<Vec<String>>()
                                 .join("\n")
                                 .not_empty_or("There are no daily puzzles set up"),
                             false,
                         )
+                        .field(
+                            "Star Notifications",
+                            star_notify_configs
+                                .iter()
+                                .map(|config| {
+                                    format!(
+                                        "<#{}>{}",
+                                        config.0,
+                                        match &config.1.board {
+                                            Some(board) => format!(" for `{board}`"),
+                                            None => String::new(),
+                                        }
+                                    )
+                                })
+                                .collect::<Vec<String>>()
+                                .join("\n")
+                                .not_empty_or("There are no star notifications set up"),
+                            false,
+                        )
+                        .field(
+                            "Role Rewards",
+                            role_reward_configs
+                                .iter()
+                                .map(|config| {
+                                    format!(
+                                        "<@&{}> for {}",
+                                        config.0,
+                                        match config.1.requirement {
+                                            RewardRequirement::Stars(stars) => {
+                                                format!("{stars} stars")
+                                            }
+                                            RewardRequirement::AllStars => {
+                                                "every star".to_owned()
+                                            }
+                                            RewardRequirement::TopRank { rank, ordering } => {
+                                                format!("top {rank} by {ordering}")
+                                            }
+                                        }
+                                    )
+                                })
+                                .collect::<Vec<String>>()
+                                .join("\n")
+                                .not_empty_or("There are no role rewards set up"),
+                            false,
+                        )
                         .color(EMBED_COLOR)
                 })
             })
@@ -89,7 +223,7 @@ pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteract
 }
 
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command
-        .name("status")
-        .description("Display any registration and dailies set up for this server")
+    command.name("status").description(
+        "Display any registration, dailies, notifications and rewards set up for this server",
+    )
 }
@@ -0,0 +1,98 @@
+use crate::bot::Bot;
+use crate::config::DEFAULT_TIMEZONE;
+use crate::format::{make_message_embed, ResponseReason};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::prelude::Context;
+
+use super::{extract_string_option, CommandOptions};
+
+struct TimezoneCommandOptions {
+    timezone: Option<String>,
+}
+
+impl CommandOptions for TimezoneCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            timezone: extract_string_option(options_list, "timezone"),
+        }
+    }
+}
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Parse options
+    let options = TimezoneCommandOptions::from_options_list(&command.data.options);
+    let timezone = match options
+        .timezone
+        .as_deref()
+        .map(str::parse::<Tz>)
+        .transpose()
+    {
+        Ok(timezone) => timezone,
+        Err(_) => {
+            command
+                .create_interaction_response(&ctx.http, |response| {
+                    response.interaction_response_data(|message| {
+                        message.ephemeral(true).add_embed(make_message_embed(
+                            ResponseReason::Error,
+                            "That isn't a timezone I know. Pick one of the suggestions, like `Australia/Sydney` or `Europe/London`.",
+                        ))
+                    })
+                })
+                .await
+                .expect("to respond to command");
+            return;
+        }
+    };
+
+    // Save data, and let the daily thread know its schedules have moved
+    bot.storage
+        .set_guild_timezone(command.guild_id.expect("Expected guild ID"), timezone)
+        .expect("Failed to save config");
+    bot.daily_changes.notify_one();
+
+    // Respond
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|message| {
+                message.ephemeral(true).add_embed(make_message_embed(
+                    ResponseReason::Success,
+                    &format!(
+                        "Times in this server are now in `{}`, including the hours and schedules of dailies.",
+                        timezone.unwrap_or(DEFAULT_TIMEZONE).name()
+                    ),
+                ))
+            })
+        })
+        .await
+        .expect("to respond to command");
+}
+
+/// Timezone names that match what's been typed so far
+pub fn timezone_choices(query: &str) -> Vec<(String, String)> {
+    TZ_VARIANTS
+        .iter()
+        .map(|timezone| timezone.name())
+        .filter(|name| name.to_lowercase().contains(query))
+        .map(|name| (name.to_owned(), name.to_owned()))
+        .collect()
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("timezone")
+        .description("Pick the timezone used for dailies and times shown in this server")
+        .create_option(|option| {
+            option
+                .name("timezone")
+                .description(
+                    "A timezone like Australia/Sydney (default: EST, when AoC releases puzzles)",
+                )
+                .kind(CommandOptionType::String)
+                .set_autocomplete(true)
+        })
+}
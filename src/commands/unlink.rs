@@ -0,0 +1,126 @@
+use crate::bot::Bot;
+use crate::calendar::EventCalendar;
+use crate::format::{make_message_embed, ResponseReason};
+use crate::rewards::remove_reward_roles;
+use chrono::Utc;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::prelude::Context;
+
+use super::link::find_member;
+use super::{extract_string_option, is_admin, CommandOptions};
+
+struct UnlinkCommandOptions {
+    member: Option<String>,
+}
+
+impl CommandOptions for UnlinkCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            member: extract_string_option(options_list, "member"),
+        }
+    }
+}
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Parse options
+    let options = UnlinkCommandOptions::from_options_list(&command.data.options);
+    let guild_id = command.guild_id.expect("Expected guild ID");
+
+    // Defer response, getting the leaderboard and removing roles can take a while
+    command.defer_ephemeral(&ctx.http).await.unwrap();
+
+    // Work out which member to unlink, if it's not the user running the command
+    let member_id = match &options.member {
+        Some(query) => bot
+            .get_registered_leaderboard(guild_id, None, EventCalendar::current(Utc::now()).year())
+            .await
+            .ok()
+            .and_then(|leaderboard| find_member(&leaderboard.leaderboard, query).map(|m| m.id)),
+        None => None,
+    };
+
+    // Save data, keeping track of who was unlinked
+    let mut unlinked_user = None;
+    let (reason, text) = {
+        let links = bot
+            .storage
+            .member_links(guild_id)
+            .expect("Failed to load config");
+        match (&options.member, member_id) {
+            (Some(_), None) => (
+                ResponseReason::Error,
+                "Couldn't find that member on this server's leaderboard.",
+            ),
+            // Admins can unlink anyone, everyone else can only unlink themselves
+            (Some(_), Some(member_id))
+                if links.get(&member_id) != Some(&command.user.id) && !is_admin(command) =>
+            {
+                (
+                    ResponseReason::Error,
+                    "Only members with the Manage Server permission can unlink other users.",
+                )
+            }
+            (Some(_), Some(member_id)) => match bot
+                .storage
+                .unlink_member(guild_id, member_id)
+                .expect("Failed to save config")
+            {
+                Some(user_id) => {
+                    unlinked_user = Some(user_id);
+                    (ResponseReason::Success, "Successfully removed that link.")
+                }
+                None => (ResponseReason::Error, "That member isn't linked to anyone."),
+            },
+            (None, _) => {
+                let unlinked = bot
+                    .storage
+                    .unlink_user(guild_id, command.user.id)
+                    .expect("Failed to save config");
+                if unlinked {
+                    unlinked_user = Some(command.user.id);
+                    (
+                        ResponseReason::Success,
+                        "Successfully unlinked your account.",
+                    )
+                } else {
+                    (
+                        ResponseReason::Error,
+                        "Your account isn't linked to anyone on the leaderboard. You can set up a link with `/link`.",
+                    )
+                }
+            }
+        }
+    };
+
+    // They no longer have the member's reward roles
+    if let Some(user_id) = unlinked_user {
+        remove_reward_roles(ctx, &*bot.storage, guild_id, user_id).await;
+    }
+
+    // Respond
+    command
+        .create_followup_message(&ctx.http, |message| {
+            message
+                .ephemeral(true)
+                .add_embed(make_message_embed(reason, text))
+        })
+        .await
+        .expect("to respond to command");
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("unlink")
+        .description("Remove the link between your discord account and the leaderboard")
+        .create_option(|option| {
+            option
+                .name("member")
+                .description("Unlink a specific member instead (requires the Manage Server permission for others)")
+                .kind(CommandOptionType::String)
+                .set_autocomplete(true)
+        })
+}
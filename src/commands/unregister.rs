@@ -1,26 +1,62 @@
 use crate::bot::Bot;
-use crate::config::Config;
 use crate::format::{make_message_embed, ResponseReason};
 use serenity::builder::CreateApplicationCommand;
-use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
 use serenity::prelude::Context;
 
-pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+use super::{add_board_option, extract_string_option, CommandOptions};
+
+struct UnregisterCommandOptions {
+    board: Option<String>,
+}
+
+impl CommandOptions for UnregisterCommandOptions {
+    fn from_options_list(options_list: &[CommandDataOption]) -> Self {
+        Self {
+            board: extract_string_option(options_list, "board"),
+        }
+    }
+}
+
+pub async fn run(bot: &Bot, ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Parse options
+    let options = UnregisterCommandOptions::from_options_list(&command.data.options);
+    let guild_id = command.guild_id.expect("Expected guild ID");
+
+    // Find the board to remove, the default one if none was picked
+    let guild_config = bot
+        .storage
+        .guild_config(guild_id, options.board.as_deref())
+        .expect("Failed to load config");
+
     // Save data
-    let mut config = Config::get().expect("Failed to load config");
-    let removed_guild_config = config
-        .guild_configs
-        .remove(&command.guild_id.expect("Expected guild ID"));
+    let removed_guild_config = match &guild_config {
+        Some(guild_config) => bot
+            .storage
+            .remove_guild_config(guild_id, &guild_config.name)
+            .expect("Failed to save config"),
+        None => false,
+    };
 
     // Respond
     command
         .create_interaction_response(&ctx.http, |response| {
             response.interaction_response_data(|message| {
-                if removed_guild_config.is_some() {
+                if let Some(guild_config) = guild_config.filter(|_| removed_guild_config) {
                     // TODO: Clear dailies here maybe?
                     message.ephemeral(true).add_embed(make_message_embed(
                         ResponseReason::Success,
-                        "Successfully unregistered this server.",
+                        &format!(
+                            "Successfully unregistered the `{}` leaderboard from this server.",
+                            guild_config.name
+                        ),
+                    ))
+                } else if let Some(board) = &options.board {
+                    message.ephemeral(true).add_embed(make_message_embed(
+                        ResponseReason::Error,
+                        &format!("There is no leaderboard called `{board}` registered to this server."),
                     ))
                 } else {
                     message.ephemeral(true).add_embed(make_message_embed(
@@ -37,5 +73,8 @@ pub async fn run(_bot: &Bot, ctx: &Context, command: &ApplicationCommandInteract
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
     command
         .name("unregister")
-        .description("Remove a registered leaderboard and session token from this server")
+        .description(
+            "Remove a registered leaderboard and its session token or view key from this server",
+        )
+        .create_option(add_board_option)
 }
@@ -1,81 +1,227 @@
-use std::{
-    collections::HashMap,
-    fs::{read_to_string, write},
-    io,
-};
+use std::{collections::HashMap, fs::read_to_string, io};
 
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use serenity::model::prelude::{ChannelId, GuildId};
-use strum::{Display, EnumString};
+use serenity::model::prelude::{ChannelId, GuildId, RoleId, UserId};
+
+use crate::{
+    aoc::{LeaderboardAuth, COMBINED_ID_SEPARATOR},
+    schedule::Schedule,
+};
+use strum::{Display, EnumIter, EnumString};
+
+pub const CONFIG_FILE: &str = "config.json";
+
+/// The name given to a leaderboard registered without one
+pub const DEFAULT_BOARD_NAME: &str = "default";
+
+/// The timezone of guilds that haven't picked one, which is also when AoC releases puzzles
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::EST;
 
-const CONFIG_FILE: &str = "config.json";
+/// How many posts of a daily can fail in a row before it's turned off
+pub const MAX_DAILY_FAILURES: u32 = 3;
 
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, EnumString, Display, Serialize, Deserialize, Clone, Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumString,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
 )]
 pub enum LeaderboardOrdering {
     LocalScore,
     GlobalScore,
     Stars,
+    ComputedScore,
+    StarsThenLastStar,
+    MedianSolveTime,
+    TotalSolveTime,
+    AveragePartDelta,
+    CurrentStreak,
 }
 
+/// A leaderboard registered to a guild. A guild can have several, one of which is its default.
 #[derive(Serialize, Deserialize)]
 pub struct GuildConfig {
-    pub session_token: String,
+    #[serde(default = "default_board_name")]
+    pub name: String,
+    #[serde(default = "default_is_default")]
+    pub is_default: bool,
+    #[serde(flatten)]
+    pub auth: LeaderboardAuth,
+    /// One leaderboard ID, or several separated by commas to combine them into one
     pub leaderboard_id: String,
 }
 
+impl GuildConfig {
+    /// The IDs of every leaderboard this combines, usually just one
+    pub fn leaderboard_ids(&self) -> Vec<&str> {
+        self.leaderboard_id
+            .split(COMBINED_ID_SEPARATOR)
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .collect()
+    }
+}
+
+fn default_board_name() -> String {
+    DEFAULT_BOARD_NAME.to_owned()
+}
+
+// Configs saved before multiple leaderboards existed only have one per guild
+fn default_is_default() -> bool {
+    true
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DailyLeaderboardConfig {
     pub guild_id: GuildId,
+    /// The hour to post at every day, if there's no schedule
     pub hour: usize,
     pub ordering: LeaderboardOrdering,
+    /// Which of the guild's leaderboards to post, or the default if not set
+    #[serde(default)]
+    pub board: Option<String>,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// When the post that was last sent was due, or when the daily was set up if it hasn't posted yet
+    #[serde(skip)]
+    pub last_posted: Option<DateTime<Utc>>,
+    /// How many posts in a row have failed
+    #[serde(skip)]
+    pub failures: u32,
+}
+
+impl DailyLeaderboardConfig {
+    /// When to post, every day at `hour` unless a schedule was given
+    pub fn posting_schedule(&self) -> Schedule {
+        self.schedule
+            .clone()
+            .unwrap_or_else(|| Schedule::at_hour(self.hour))
+    }
+
+    /// Whether the daily was turned off after failing too many times
+    pub fn is_disabled(&self) -> bool {
+        self.failures >= MAX_DAILY_FAILURES
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct DailyPuzzleConfig {
     pub guild_id: GuildId,
+    /// The hour to post at every day, if there's no schedule
     pub hour: usize,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// When the post that was last sent was due, or when the daily was set up if it hasn't posted yet
+    #[serde(skip)]
+    pub last_posted: Option<DateTime<Utc>>,
+    /// How many posts in a row have failed
+    #[serde(skip)]
+    pub failures: u32,
 }
 
+impl DailyPuzzleConfig {
+    /// When to post, every day at `hour` unless a schedule was given
+    pub fn posting_schedule(&self) -> Schedule {
+        self.schedule
+            .clone()
+            .unwrap_or_else(|| Schedule::at_hour(self.hour))
+    }
+
+    /// Whether the daily was turned off after failing too many times
+    pub fn is_disabled(&self) -> bool {
+        self.failures >= MAX_DAILY_FAILURES
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StarNotifyConfig {
+    pub guild_id: GuildId,
+    /// Which of the guild's leaderboards to watch, or the default if not set
+    #[serde(default)]
+    pub board: Option<String>,
+}
+
+/// What a member of the leaderboard has to achieve to be given a role
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum RewardRequirement {
+    /// At least this many stars
+    Stars(usize),
+    /// Every star of the event
+    AllStars,
+    /// Placed in the top `rank` of the leaderboard
+    TopRank {
+        rank: usize,
+        ordering: LeaderboardOrdering,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RoleRewardConfig {
+    pub guild_id: GuildId,
+    pub requirement: RewardRequirement,
+}
+
+/// Everything the bot used to store in `config.json`, now only read to import it into storage
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub guild_configs: HashMap<GuildId, GuildConfig>,
     pub daily_leaderboard_configs: HashMap<ChannelId, DailyLeaderboardConfig>,
     pub daily_puzzle_configs: HashMap<ChannelId, DailyPuzzleConfig>,
+    #[serde(default)]
+    pub star_notify_configs: HashMap<ChannelId, StarNotifyConfig>,
+    /// Discord users linked to AoC members, keyed by AoC member ID
+    #[serde(default)]
+    pub member_links: HashMap<GuildId, HashMap<usize, UserId>>,
+    #[serde(default)]
+    pub role_reward_configs: HashMap<RoleId, RoleRewardConfig>,
 }
 
 impl Config {
-    fn new() -> Config {
-        Config {
-            guild_configs: HashMap::new(),
-            daily_leaderboard_configs: HashMap::new(),
-            daily_puzzle_configs: HashMap::new(),
-        }
-    }
-
-    pub fn get() -> Result<Self, std::io::Error> {
-        // your turn...
-        match read_to_string(CONFIG_FILE) {
-            Ok(json) => {
-                let config: Self = serde_json::from_str(&json)?;
-                Ok(config)
-            }
+    /// Read a config file from before storage moved to a database, if there is one
+    pub fn load(path: &str) -> io::Result<Option<Self>> {
+        match read_to_string(path) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
             Err(err) => match err.kind() {
-                io::ErrorKind::NotFound => Ok(Config::new()),
+                io::ErrorKind::NotFound => Ok(None),
                 _ => Err(err),
             },
         }
     }
-
-    pub fn save(&self) -> Result<(), std::io::Error> {
-        write(CONFIG_FILE, serde_json::to_string(self)?) // @ando: why can't we use Box<dyn Error> plz help tyvm
-    }
 }
 
-impl Drop for Config {
-    // Attempts to save the config when it goes out of scope
-    fn drop(&mut self) {
-        self.save().ok();
+#[cfg(test)]
+mod test {
+    use super::GuildConfig;
+    use crate::aoc::LeaderboardAuth;
+
+    #[test]
+    fn test_guild_config_auth_format() {
+        // Configs saved before view keys existed only have a session token
+        let config: GuildConfig =
+            serde_json::from_str(r#"{"session_token":"abc","leaderboard_id":"1234"}"#).unwrap();
+        assert!(matches!(config.auth, LeaderboardAuth::SessionToken(ref token) if token == "abc"));
+
+        let config: GuildConfig =
+            serde_json::from_str(r#"{"view_key":"def","leaderboard_id":"1234"}"#).unwrap();
+        assert!(matches!(config.auth, LeaderboardAuth::ViewKey(ref key) if key == "def"));
+
+        // Configs saved before multiple leaderboards existed become each guild's default
+        assert_eq!(config.name, "default");
+        assert!(config.is_default);
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"name":"default","is_default":true,"view_key":"def","leaderboard_id":"1234"}"#
+        );
     }
 }
@@ -1,143 +1,971 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, env, fmt, future::Future, sync::Arc};
 
-use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
-use serenity::{model::prelude::Activity, prelude::Context};
-use tokio::{join, sync::Mutex};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use reqwest::StatusCode;
+use serenity::{
+    builder::CreateEmbed,
+    http::HttpError,
+    model::prelude::{Activity, ChannelId, GuildId},
+    prelude::Context,
+};
+use tokio::{join, sync::Notify};
+
+use chrono_tz::Tz;
 
 use crate::{
-    aoc::AOCData,
-    config::Config,
-    format::{make_leaderboard_embed, make_puzzle_embed},
+    aoc::{AOCData, AocApi, AocError, RequestGovernor},
+    calendar::{est, EventCalendar},
+    clock::{Clock, SystemClock},
+    config::{DailyLeaderboardConfig, DEFAULT_TIMEZONE, MAX_DAILY_FAILURES},
+    format::{make_leaderboard_embed, make_message_embed, make_puzzle_embed, ResponseReason},
+    links::{linked_member_names, MemberNames},
+    schedule::Schedule,
+    storage::{SharedStorage, Storage, StorageError, StorageResult},
 };
 
-pub const EST_SECS: i32 = -5 * 60 * 60;
+const DEFAULT_GRACE_MINS: i64 = 6 * 60;
+
+/// How many times to try fetching or sending a daily if it fails in a way that might not last
+const POST_ATTEMPTS: u32 = 3;
 
-pub fn is_puzzle_day(time: &DateTime<FixedOffset>) -> bool {
-    time.month() == 12 && time.day() <= 12
+/// How long to wait before trying a daily again, doubled after each attempt
+const RETRY_DELAY_SECS: i64 = 30;
+
+pub async fn daily_posts(
+    aoc_data: Arc<AOCData>,
+    storage: SharedStorage,
+    ctx: Context,
+    changes: Arc<Notify>,
+) {
+    DailyPoster {
+        clock: &SystemClock,
+        sink: &ctx,
+        storage: &*storage,
+        aoc_data: &aoc_data,
+    }
+    .run(&changes)
+    .await
 }
 
-pub async fn daily_posts(aoc_data: Arc<Mutex<AOCData>>, ctx: Context) {
-    // Create EST timezone
-    let tz = FixedOffset::east_opt(EST_SECS).unwrap();
+/// Where the daily scheduler sends what it posts
+#[async_trait]
+pub trait MessageSink: Send + Sync {
+    /// Send an embed to a channel, with some text above it
+    async fn send_embed(
+        &self,
+        channel_id: ChannelId,
+        content: Option<String>,
+        embed: CreateEmbed,
+    ) -> serenity::Result<()>;
 
-    loop {
-        // Get current time in EST
-        let time = Utc::now().with_timezone(&tz);
+    /// The channel a guild gets notices from discord in, if it has one
+    async fn system_channel(&self, guild_id: GuildId) -> Option<ChannelId>;
 
-        // Wait until the hour
-        let mins_until_hour = 60 - time.minute() as u64;
-        log::info!("Waiting for {mins_until_hour} mins until next hour...");
-        tokio::time::sleep(Duration::from_secs(60 * mins_until_hour)).await;
+    /// The discord names of AoC members linked to users in a guild
+    async fn member_names(&self, storage: &dyn Storage, guild_id: GuildId) -> MemberNames;
 
-        // Get the current time after waiting
-        let time = Utc::now().with_timezone(&tz);
-        let year = time.year() as usize;
+    /// Show what the bot is doing next to its name
+    async fn set_activity(&self, activity: Activity);
+}
+
+#[async_trait]
+impl MessageSink for Context {
+    async fn send_embed(
+        &self,
+        channel_id: ChannelId,
+        content: Option<String>,
+        embed: CreateEmbed,
+    ) -> serenity::Result<()> {
+        channel_id
+            .send_message(&self.http, |message| {
+                if let Some(content) = content {
+                    message.content(content);
+                }
+                message.set_embed(embed)
+            })
+            .await
+            .map(|_| ())
+    }
+
+    async fn system_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        match guild_id.to_partial_guild(&self.http).await {
+            Ok(guild) => guild.system_channel_id,
+            Err(error) => {
+                log::warn!("Failed to get guild {guild_id} to find its system channel: {error}");
+                None
+            }
+        }
+    }
+
+    async fn member_names(&self, storage: &dyn Storage, guild_id: GuildId) -> MemberNames {
+        linked_member_names(self, storage, guild_id).await
+    }
+
+    async fn set_activity(&self, activity: Activity) {
+        Context::set_activity(self, activity).await
+    }
+}
+
+/// Everything needed to post dailies, so the scheduler can run against a simulated clock and
+/// discord in tests
+pub struct DailyPoster<'a, A = RequestGovernor> {
+    pub clock: &'a dyn Clock,
+    pub sink: &'a dyn MessageSink,
+    pub storage: &'a dyn Storage,
+    pub aoc_data: &'a AOCData<A>,
+}
 
-        // Is it an AOC puzzle ay?
-        if !is_puzzle_day(&time) {
-            log::info!("Not a puzzle day, skipping daily posts");
+impl<A: AocApi + 'static> DailyPoster<'_, A> {
+    /// Post every daily when it's due, forever. Notifying `changes` makes the scheduler check
+    /// for dailies that were added or changed.
+    pub async fn run(&self, changes: &Notify) {
+        let tz = est();
 
-            // Set our activity
-            ctx.set_activity(Activity::playing("Waiting for Advent of Code"))
+        // Dailies due at or before this time have already been posted
+        let mut posted_until = self.clock.now();
+
+        // Catch up on anything missed while we were offline
+        self.post_missed_dailies(posted_until).await;
+
+        loop {
+            // Set our activity for the current day
+            let now = self.clock.now();
+            self.sink
+                .set_activity(match EventCalendar::current(now).puzzle_day(now) {
+                    Some(day) => Activity::playing(format!("Advent of Code Day {day}")),
+                    None => Activity::playing("Waiting for Advent of Code"),
+                })
                 .await;
 
-            // Keep waiting...
-            continue;
+            // Sleep until the next daily is due, or until midnight to update our activity
+            let next_post = next_daily_time(self.storage, posted_until).unwrap_or_else(|error| {
+                log::error!("Failed to load dailies: {error}");
+                None
+            });
+            let midnight = Schedule::at_hour(0)
+                .next_after(&now.with_timezone(&tz))
+                .expect("midnight comes every day")
+                .with_timezone(&Utc);
+            let wake_time = next_post.map_or(midnight, |next_post| next_post.min(midnight));
+            log::info!(
+                "Waiting until {} for the next daily post",
+                wake_time.with_timezone(&tz).format("[%Y-%m-%d][%H:%M]")
+            );
+            tokio::select! {
+                _ = self.clock.sleep_until(wake_time) => {}
+
+                // A daily was added or changed, so it may be due sooner
+                _ = changes.notified() => {
+                    posted_until = posted_until.max(self.clock.now());
+                    continue;
+                }
+            }
+            posted_until = wake_time;
+            if next_post != Some(wake_time) {
+                continue;
+            }
+
+            // Is it an AOC puzzle ay?
+            if !is_puzzle_day(wake_time) {
+                log::info!("Not a puzzle day, skipping daily posts");
+                continue;
+            }
+
+            log::info!(
+                "The current EST time is {}",
+                wake_time.with_timezone(&tz).format("[%Y-%m-%d][%H:%M:%S]")
+            );
+
+            // Post embeds
+            join!(
+                self.post_daily_leaderboards(wake_time),
+                self.post_daily_puzzles(wake_time)
+            );
         }
+    }
+
+    /// Send any dailies that were due while the bot was offline, marked as delayed
+    async fn post_missed_dailies(&self, now: DateTime<Utc>) {
+        let storage = self.storage;
+        let lb_configs = storage.daily_leaderboard_configs().unwrap_or_else(|error| {
+            log::error!("Failed to load daily leaderboards: {error}");
+            HashMap::new()
+        });
+        let missed_leaderboards = lb_configs.iter().filter_map(|(channel_id, lb_config)| {
+            let timezone = guild_timezone(storage, lb_config.guild_id);
+            let schedule = lb_config.posting_schedule();
+            let due = missed_post(&schedule, timezone, lb_config.last_posted, now)
+                .filter(|_| !lb_config.is_disabled())?;
+            log::info!("Catching up on the daily leaderboard due at {due} in {channel_id}");
+            Some(self.post_daily_leaderboard(*channel_id, lb_config, due, true))
+        });
+        join_all(missed_leaderboards).await;
+
+        let pz_configs = storage.daily_puzzle_configs().unwrap_or_else(|error| {
+            log::error!("Failed to load daily puzzles: {error}");
+            HashMap::new()
+        });
+        let missed_puzzles = pz_configs.iter().filter_map(|(channel_id, pz_config)| {
+            let timezone = guild_timezone(storage, pz_config.guild_id);
+            let schedule = pz_config.posting_schedule();
+            let due = missed_post(&schedule, timezone, pz_config.last_posted, now)
+                .filter(|_| !pz_config.is_disabled())?;
+            log::info!("Catching up on the daily puzzle due at {due} in {channel_id}");
+            Some(self.post_daily_puzzle(*channel_id, pz_config.guild_id, due, true))
+        });
+        join_all(missed_puzzles).await;
+    }
+
+    pub async fn post_daily_leaderboards(&self, time: DateTime<Utc>) {
+        // Get configs that are due now
+        let lb_configs = match self.storage.daily_leaderboard_configs() {
+            Ok(lb_configs) => lb_configs,
+            Err(error) => {
+                log::error!("Failed to load daily leaderboards: {error}");
+                return;
+            }
+        };
+        let current_configs: HashMap<_, _> = lb_configs
+            .into_iter()
+            .filter(|config| {
+                let timezone = guild_timezone(self.storage, config.1.guild_id);
+                !config.1.is_disabled() && is_due(&config.1.posting_schedule(), timezone, time)
+            })
+            .collect();
+        log::info!("Found {} leaderboards to be posted", current_configs.len());
+
+        // Post embeds, each on its own so one channel failing or retrying doesn't hold up the rest
+        join_all(current_configs.iter().map(|(channel_id, lb_config)| {
+            self.post_daily_leaderboard(*channel_id, lb_config, time, false)
+        }))
+        .await;
+    }
+
+    /// Send a daily leaderboard that was due at a time, noting that it's late if it's `delayed`
+    async fn post_daily_leaderboard(
+        &self,
+        channel_id: ChannelId,
+        lb_config: &DailyLeaderboardConfig,
+        due: DateTime<Utc>,
+        delayed: bool,
+    ) {
+        let result = self
+            .try_post_daily_leaderboard(channel_id, lb_config, due, delayed)
+            .await;
+        self.record_post(
+            DailyKind::Leaderboard,
+            channel_id,
+            lb_config.guild_id,
+            due,
+            result,
+        )
+        .await;
+    }
+
+    async fn try_post_daily_leaderboard(
+        &self,
+        channel_id: ChannelId,
+        lb_config: &DailyLeaderboardConfig,
+        due: DateTime<Utc>,
+        delayed: bool,
+    ) -> Result<(), PostError> {
+        // Get guild config
+        let guild_config = self
+            .storage
+            .guild_config(lb_config.guild_id, lb_config.board.as_deref())?
+            .ok_or_else(|| PostError::NotRegistered(lb_config.board.clone()))?;
+
+        // Get leaderboard, trying again if AoC is having trouble
+        let aoc_data = self.aoc_data;
+        let year = &EventCalendar::current(due).year().to_string();
+        let leaderboard_ids = &guild_config.leaderboard_ids();
+        let auth = &guild_config.auth;
+        let leaderboard = self
+            .with_retries(
+                "fetch leaderboard for daily post",
+                AocError::is_temporary,
+                move || aoc_data.get_combined_leaderboard(year, leaderboard_ids, auth, false),
+            )
+            .await;
 
-        let day = time.day() as usize;
-        let hour = time.hour() as usize;
-        log::info!(
-            "The current EST time is {}",
-            time.format("[%Y-%m-%d][%H:%M:%S]")
+        // Create and send embed, explaining what went wrong if we couldn't get the leaderboard
+        let leaderboard = match leaderboard {
+            Ok(leaderboard) => leaderboard,
+            Err(error) => {
+                let embed = make_message_embed(
+                    ResponseReason::Error,
+                    &format!(
+                        "Couldn't fetch the leaderboard for today's post. {}",
+                        error.user_message()
+                    ),
+                );
+                self.send_daily(channel_id, embed, due, delayed).await?;
+                return Err(PostError::Leaderboard(error));
+            }
+        };
+        let names = self
+            .sink
+            .member_names(self.storage, lb_config.guild_id)
+            .await;
+        let embed = make_leaderboard_embed(leaderboard, lb_config.ordering, &names);
+        self.send_daily(channel_id, embed, due, delayed).await
+    }
+
+    pub async fn post_daily_puzzles(&self, time: DateTime<Utc>) {
+        // Get configs that are due now
+        let pz_configs = match self.storage.daily_puzzle_configs() {
+            Ok(pz_configs) => pz_configs,
+            Err(error) => {
+                log::error!("Failed to load daily puzzles: {error}");
+                return;
+            }
+        };
+        let current_configs: HashMap<_, _> = pz_configs
+            .into_iter()
+            .filter(|config| {
+                let timezone = guild_timezone(self.storage, config.1.guild_id);
+                !config.1.is_disabled() && is_due(&config.1.posting_schedule(), timezone, time)
+            })
+            .collect();
+        log::info!("Found {} puzzles to be posted", current_configs.len());
+
+        // Post embeds, each on its own so one channel failing or retrying doesn't hold up the rest
+        join_all(current_configs.iter().map(|(channel_id, pz_config)| {
+            self.post_daily_puzzle(*channel_id, pz_config.guild_id, time, false)
+        }))
+        .await;
+    }
+
+    /// Send the puzzle for the day a daily was due, noting that it's late if it's `delayed`
+    async fn post_daily_puzzle(
+        &self,
+        channel_id: ChannelId,
+        guild_id: GuildId,
+        due: DateTime<Utc>,
+        delayed: bool,
+    ) {
+        // Post the latest puzzle as of when the daily was due
+        let calendar = EventCalendar::current(due);
+        let (year, day) = (
+            calendar.year() as usize,
+            calendar.latest_unlocked_day(due) as usize,
         );
+        let puzzle_details = self.aoc_data.get_puzzle_details(year, day).await.ok();
 
-        // Set our activity
-        ctx.set_activity(Activity::playing(format!("Advent of Code Day {day}")))
+        // Create and send embed
+        let embed = make_puzzle_embed(year, day, puzzle_details, true);
+        let result = self.send_daily(channel_id, embed, due, delayed).await;
+        self.record_post(DailyKind::Puzzle, channel_id, guild_id, due, result)
             .await;
+    }
 
-        // Get config
-        let config = Config::get().expect("Failed to get config");
+    /// Send a daily's embed, trying again if discord is having trouble
+    async fn send_daily(
+        &self,
+        channel_id: ChannelId,
+        embed: CreateEmbed,
+        due: DateTime<Utc>,
+        delayed: bool,
+    ) -> Result<(), PostError> {
+        let sink = self.sink;
+        let embed = &embed;
+        let content = delayed.then(|| {
+            format!(
+                "⏰ This post is late, it was due <t:{}:R>.",
+                due.timestamp()
+            )
+        });
+        let content = &content;
+        self.with_retries(
+            "send daily post",
+            is_temporary_send_error,
+            move || async move {
+                sink.send_embed(channel_id, content.clone(), embed.clone())
+                    .await
+            },
+        )
+        .await
+        .map_err(PostError::Send)
+    }
 
-        // Post embeds
-        let lb_task = post_daily_leaderboards(&ctx, &config, year, hour, aoc_data.clone());
-        let pz_task = post_daily_puzzles(&ctx, &config, year, day, hour, aoc_data.clone());
-        join!(lb_task, pz_task);
+    /// Try something a few times if it fails in a way that might not last, waiting longer each
+    /// time
+    async fn with_retries<T, E, F>(
+        &self,
+        action: &str,
+        is_temporary: impl Fn(&E) -> bool,
+        mut attempt: impl FnMut() -> F,
+    ) -> Result<T, E>
+    where
+        E: fmt::Display,
+        F: Future<Output = Result<T, E>>,
+    {
+        let mut delay = chrono::Duration::seconds(RETRY_DELAY_SECS);
+        for _ in 1..POST_ATTEMPTS {
+            match attempt().await {
+                Err(error) if is_temporary(&error) => {
+                    log::warn!(
+                        "Failed to {action}, trying again in {}s: {error}",
+                        delay.num_seconds()
+                    );
+                    self.clock.sleep_until(self.clock.now() + delay).await;
+                    delay = delay * 2;
+                }
+                result => return result,
+            }
+        }
+        attempt().await
     }
-}
 
-pub async fn post_daily_leaderboards(
-    ctx: &Context,
-    config: &Config,
-    year: usize,
-    hour: usize,
-    aoc_data: Arc<Mutex<AOCData>>,
-) {
-    // Get data
-    let mut aoc_data = aoc_data.lock().await;
-
-    // Get configs to be posted this hour
-    let current_configs: HashMap<_, _> = config
-        .daily_leaderboard_configs
-        .iter()
-        .filter(|config| config.1.hour == hour)
-        .collect();
-    log::info!("Found {} leaderboards to be posted", current_configs.len());
-
-    // Post embeds
-    for (channel_id, lb_config) in current_configs {
-        // Get guild config
-        if let Some(guild_config) = config.guild_configs.get(&lb_config.guild_id) {
-            // Get leaderboard
-            let leaderboard = aoc_data
-                .get_leaderboard(
-                    &year.to_string(),
-                    &guild_config.leaderboard_id,
-                    &guild_config.session_token,
-                    false,
-                )
-                .await
-                .expect("Failed to get leaderboard");
+    /// Remember how posting a daily went, turning it off if it has failed too many times in a row
+    async fn record_post(
+        &self,
+        kind: DailyKind,
+        channel_id: ChannelId,
+        guild_id: GuildId,
+        due: DateTime<Utc>,
+        result: Result<(), PostError>,
+    ) {
+        let storage = self.storage;
+        let error = match result {
+            Ok(()) => {
+                let saved = match kind {
+                    DailyKind::Leaderboard => storage.set_daily_leaderboard_posted(channel_id, due),
+                    DailyKind::Puzzle => storage.set_daily_puzzle_posted(channel_id, due),
+                };
+                if let Err(error) = saved {
+                    log::error!(
+                        "Failed to save that the {} in {channel_id} was posted: {error}",
+                        kind.name()
+                    );
+                }
+                return;
+            }
+
+            // Our own problems don't count against the guild
+            Err(PostError::Storage(error)) => {
+                log::error!(
+                    "Failed to post the {} in {channel_id}: {error}",
+                    kind.name()
+                );
+                return;
+            }
+
+            Err(error) => error,
+        };
+        log::warn!(
+            "Failed to post the {} in {channel_id}: {error}",
+            kind.name()
+        );
+
+        let failures = match kind {
+            DailyKind::Leaderboard => storage.add_daily_leaderboard_failure(channel_id),
+            DailyKind::Puzzle => storage.add_daily_puzzle_failure(channel_id),
+        };
+        match failures {
+            Ok(failures) if failures == MAX_DAILY_FAILURES => {
+                log::warn!(
+                    "Turning off the {} in {channel_id} after {failures} failed posts",
+                    kind.name()
+                );
+                self.send_disabled_notice(kind, channel_id, guild_id, &error)
+                    .await;
+            }
+            Ok(_) => {}
+            Err(error) => log::error!(
+                "Failed to save that the {} in {channel_id} failed: {error}",
+                kind.name()
+            ),
+        }
+    }
 
-            // Create and send embed
-            let embed = make_leaderboard_embed(leaderboard, lb_config.ordering);
-            channel_id
-                .send_message(&ctx.http, |message| message.set_embed(embed))
+    /// Let a guild know why one of its dailies was turned off, in the daily's channel if
+    /// possible or else the guild's system channel
+    async fn send_disabled_notice(
+        &self,
+        kind: DailyKind,
+        channel_id: ChannelId,
+        guild_id: GuildId,
+        error: &PostError,
+    ) {
+        let embed = make_message_embed(
+            ResponseReason::Error,
+            &format!(
+                "The {} in <#{channel_id}> has been turned off after failing {MAX_DAILY_FAILURES} times in a row. {}\n\n\
+                Once that's fixed, run `{}` again to turn it back on.",
+                kind.name(),
+                error.user_message(),
+                kind.command(),
+            ),
+        );
+
+        if self
+            .sink
+            .send_embed(channel_id, None, embed.clone())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        if let Some(system_channel_id) = self.sink.system_channel(guild_id).await {
+            if self
+                .sink
+                .send_embed(system_channel_id, None, embed)
                 .await
-                .expect("Failed to send embed");
+                .is_ok()
+            {
+                return;
+            }
         }
+        log::warn!(
+            "Couldn't let guild {guild_id} know that the {} in {channel_id} was turned off",
+            kind.name()
+        );
     }
 }
 
-pub async fn post_daily_puzzles(
-    ctx: &Context,
-    config: &Config,
-    year: usize,
-    day: usize,
-    hour: usize,
-    aoc_data: Arc<Mutex<AOCData>>,
-) {
-    // Get configs to be posted this hour
-    let current_configs: HashMap<_, _> = config
-        .daily_puzzle_configs
-        .iter()
-        .filter(|config| config.1.hour == hour)
-        .collect();
-    log::info!("Found {} puzzles to be posted", current_configs.len());
-
-    let puzzle_details = {
-        let mut aoc_data = aoc_data.lock().await;
-        aoc_data.get_puzzle_details(year, day).await
-    }
-    .ok();
-
-    // Post embeds
-    for channel_id in current_configs.keys() {
-        // Create and send embed
-        let embed = make_puzzle_embed(year, day, puzzle_details.clone(), true);
-        channel_id
-            .send_message(&ctx.http, |message| message.set_embed(embed))
-            .await
-            .expect("Failed to send embed");
+/// Whether puzzles are being released on the day of a time, in AoC's own timezone
+fn is_puzzle_day(time: DateTime<Utc>) -> bool {
+    EventCalendar::current(time).is_running(time)
+}
+
+/// The next time after `time` that a daily is due, with its schedule in its guild's timezone
+fn next_due(schedule: &Schedule, timezone: Tz, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule
+        .next_after(&time.with_timezone(&timezone))
+        .map(|due| due.with_timezone(&Utc))
+}
+
+/// Whether a daily is due at a time, with its schedule in its guild's timezone
+fn is_due(schedule: &Schedule, timezone: Tz, time: DateTime<Utc>) -> bool {
+    schedule.matches(&time.with_timezone(&timezone))
+}
+
+/// The timezone a guild's dailies are scheduled in, or the default if it can't be loaded
+pub fn guild_timezone(storage: &dyn Storage, guild_id: GuildId) -> Tz {
+    storage
+        .guild_timezone_or_default(guild_id)
+        .unwrap_or_else(|error| {
+            log::error!("Failed to load the timezone of guild {guild_id}: {error}");
+            DEFAULT_TIMEZONE
+        })
+}
+
+/// The next time after `time` that any daily that's still turned on is due
+fn next_daily_time(
+    storage: &dyn Storage,
+    time: DateTime<Utc>,
+) -> StorageResult<Option<DateTime<Utc>>> {
+    let leaderboard_schedules = storage
+        .daily_leaderboard_configs()?
+        .into_values()
+        .filter(|config| !config.is_disabled())
+        .map(|config| (config.guild_id, config.posting_schedule()));
+    let puzzle_schedules = storage
+        .daily_puzzle_configs()?
+        .into_values()
+        .filter(|config| !config.is_disabled())
+        .map(|config| (config.guild_id, config.posting_schedule()));
+    Ok(leaderboard_schedules
+        .chain(puzzle_schedules)
+        .filter_map(|(guild_id, schedule)| {
+            next_due(&schedule, guild_timezone(storage, guild_id), time)
+        })
+        .min())
+}
+
+/// How long after a daily was due it can still be sent, if the bot was offline at the time
+fn grace_period() -> chrono::Duration {
+    let mins = env::var("DAILY_GRACE_MINS")
+        .ok()
+        .and_then(|mins| mins.parse().ok())
+        .unwrap_or(DEFAULT_GRACE_MINS);
+    chrono::Duration::minutes(mins)
+}
+
+/// The latest time a daily was due within the grace period that it hasn't been posted for
+fn missed_post(
+    schedule: &Schedule,
+    timezone: Tz,
+    last_posted: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    // Dailies are saved with when they were set up, so this is only missing for ones set up
+    // before posts were tracked, which can't tell what they've already posted
+    let mut since = last_posted?.max(now - grace_period());
+
+    let mut missed = None;
+    while let Some(due) = next_due(schedule, timezone, since).filter(|due| *due <= now) {
+        missed = Some(due);
+        since = due;
+    }
+    missed.filter(|due| is_puzzle_day(*due))
+}
+
+/// Whether discord might accept a message if it was sent again later
+fn is_temporary_send_error(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(error) => match error.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => {
+                response.status_code.is_server_error()
+                    || response.status_code == StatusCode::TOO_MANY_REQUESTS
+            }
+            HttpError::Request(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The kinds of daily post
+#[derive(Clone, Copy)]
+enum DailyKind {
+    Leaderboard,
+    Puzzle,
+}
+
+impl DailyKind {
+    fn name(self) -> &'static str {
+        match self {
+            DailyKind::Leaderboard => "daily leaderboard",
+            DailyKind::Puzzle => "daily puzzle",
+        }
+    }
+
+    /// The command that sets this kind of daily up
+    fn command(self) -> &'static str {
+        match self {
+            DailyKind::Leaderboard => "/daily leaderboard",
+            DailyKind::Puzzle => "/daily puzzle",
+        }
+    }
+}
+
+/// Why a daily couldn't be posted
+#[derive(Debug)]
+enum PostError {
+    /// The daily's config couldn't be loaded, which isn't the guild's fault
+    Storage(StorageError),
+
+    /// The leaderboard the daily posts has been unregistered
+    NotRegistered(Option<String>),
+
+    /// The leaderboard couldn't be fetched from AoC
+    Leaderboard(AocError),
+
+    /// Discord wouldn't take the message, e.g. because the channel was deleted
+    Send(serenity::Error),
+}
+
+impl PostError {
+    /// A full explanation suitable to show in discord
+    fn user_message(&self) -> String {
+        match self {
+            PostError::Storage(_) => "Something went wrong loading its settings.".to_owned(),
+            PostError::NotRegistered(Some(board)) => {
+                format!("This server no longer has a leaderboard called `{board}`.")
+            }
+            PostError::NotRegistered(None) => {
+                "This server no longer has a registered leaderboard.".to_owned()
+            }
+            PostError::Leaderboard(error) => error.user_message(),
+            PostError::Send(_) => "Couldn't send messages to that channel. Check that it still exists, and that the bot is allowed to send messages and embeds in it.".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for PostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostError::Storage(err) => write!(f, "failed to load config: {err}"),
+            PostError::NotRegistered(_) => write!(f, "leaderboard is no longer registered"),
+            PostError::Leaderboard(err) => write!(f, "failed to fetch leaderboard: {err}"),
+            PostError::Send(err) => write!(f, "failed to send message: {err}"),
+        }
+    }
+}
+
+impl From<StorageError> for PostError {
+    fn from(err: StorageError) -> Self {
+        PostError::Storage(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex as StdMutex;
+
+    use async_trait::async_trait;
+    use chrono::{TimeZone, Utc};
+    use serenity::{
+        builder::CreateEmbed,
+        model::prelude::{Activity, ChannelId, GuildId},
+    };
+    use tokio::sync::Notify;
+
+    use super::{missed_post, DailyPoster, MessageSink};
+    use crate::{
+        aoc::{AOCData, FixtureAocApi, LeaderboardAuth},
+        calendar::EventCalendar,
+        clock::SimulatedClock,
+        config::{DailyLeaderboardConfig, DailyPuzzleConfig, GuildConfig, LeaderboardOrdering},
+        links::MemberNames,
+        schedule::Schedule,
+        storage::{SqliteStorage, Storage},
+    };
+
+    const SYDNEY: GuildId = GuildId(2);
+    const LEADERBOARD: ChannelId = ChannelId(10);
+    const PUZZLE: ChannelId = ChannelId(20);
+    const UNREGISTERED: ChannelId = ChannelId(30);
+    const SYDNEY_PUZZLE: ChannelId = ChannelId(40);
+
+    /// Keeps the title of every embed sent and every activity set, instead of using discord
+    #[derive(Default)]
+    struct RecordingSink {
+        sent: StdMutex<Vec<(ChannelId, String)>>,
+        activities: StdMutex<Vec<String>>,
+    }
+
+    impl RecordingSink {
+        fn titles(&self, channel_id: ChannelId) -> Vec<String> {
+            let sent = self.sent.lock().unwrap();
+            sent.iter()
+                .filter(|(channel, _)| *channel == channel_id)
+                .map(|(_, title)| title.clone())
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl MessageSink for RecordingSink {
+        async fn send_embed(
+            &self,
+            channel_id: ChannelId,
+            _content: Option<String>,
+            embed: CreateEmbed,
+        ) -> serenity::Result<()> {
+            let title = embed.0["title"].as_str().unwrap_or_default().to_owned();
+            self.sent.lock().unwrap().push((channel_id, title));
+            Ok(())
+        }
+
+        async fn system_channel(&self, _guild_id: GuildId) -> Option<ChannelId> {
+            None
+        }
+
+        async fn member_names(&self, _storage: &dyn Storage, _guild_id: GuildId) -> MemberNames {
+            MemberNames::new()
+        }
+
+        async fn set_activity(&self, activity: Activity) {
+            let mut activities = self.activities.lock().unwrap();
+            if activities.last() != Some(&activity.name) {
+                activities.push(activity.name);
+            }
+        }
+    }
+
+    /// Run the scheduler from the end of November to after Christmas in a year, with a daily
+    /// leaderboard and puzzle in EST, a daily puzzle in Sydney, and a daily for a leaderboard
+    /// that isn't registered
+    async fn simulate_december(year: i32) -> (RecordingSink, SqliteStorage) {
+        let est = chrono_tz::EST;
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        storage
+            .set_guild_config(
+                GuildId(1),
+                &GuildConfig {
+                    name: "default".to_owned(),
+                    is_default: true,
+                    auth: LeaderboardAuth::ViewKey("key".to_owned()),
+                    leaderboard_id: "1234".to_owned(),
+                },
+            )
+            .unwrap();
+        storage
+            .set_guild_timezone(SYDNEY, Some(chrono_tz::Australia::Sydney))
+            .unwrap();
+        let lb_config = |board: Option<&str>| DailyLeaderboardConfig {
+            guild_id: GuildId(1),
+            hour: 9,
+            ordering: LeaderboardOrdering::LocalScore,
+            board: board.map(str::to_owned),
+            schedule: None,
+            last_posted: None,
+            failures: 0,
+        };
+        let pz_config = |guild_id, hour| DailyPuzzleConfig {
+            guild_id,
+            hour,
+            schedule: None,
+            last_posted: None,
+            failures: 0,
+        };
+        storage
+            .set_daily_leaderboard_config(LEADERBOARD, &lb_config(None))
+            .unwrap();
+        storage
+            .set_daily_leaderboard_config(UNREGISTERED, &lb_config(Some("gone")))
+            .unwrap();
+        storage
+            .set_daily_puzzle_config(PUZZLE, &pz_config(GuildId(1), 0))
+            .unwrap();
+        storage
+            .set_daily_puzzle_config(SYDNEY_PUZZLE, &pz_config(SYDNEY, 9))
+            .unwrap();
+
+        let api = FixtureAocApi::default()
+            .with_leaderboard(
+                &year.to_string(),
+                "1234",
+                include_str!("../fixtures/leaderboard_2023.json"),
+            )
+            .with_puzzle(year as usize, 1, "Trebuchet?!");
+        let aoc_data = AOCData::with_api(api);
+        let sink = RecordingSink::default();
+        let clock = SimulatedClock::new(
+            est.with_ymd_and_hms(year, 11, 30, 0, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc),
+            est.with_ymd_and_hms(year, 12, 26, 23, 59, 0)
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        let poster = DailyPoster {
+            clock: &clock,
+            sink: &sink,
+            storage: &storage,
+            aoc_data: &aoc_data,
+        };
+
+        // Run through December until the scheduler waits past the end
+        let changes = Notify::new();
+        tokio::select! {
+            _ = poster.run(&changes) => unreachable!("the scheduler never stops"),
+            _ = clock.finished() => {}
+        }
+        (sink, storage)
+    }
+
+    /// Check that a simulated December posted every daily once for each puzzle of the event
+    fn assert_posted_every_day(sink: &RecordingSink, storage: &SqliteStorage, year: i32) {
+        let puzzle_count = EventCalendar::new(year).puzzle_count();
+
+        // Dailies are posted for every day puzzles are released
+        let mut puzzle_titles = vec![format!("🎁  New Puzzle: Trebuchet?! (Day 1, {year})")];
+        puzzle_titles
+            .extend((2..=puzzle_count).map(|day| format!("🎁  New Puzzle: Day {day}, {year}")));
+        assert_eq!(sink.titles(PUZZLE), puzzle_titles);
+        assert_eq!(
+            sink.titles(LEADERBOARD),
+            vec!["🏆  Leaderboard"; puzzle_count as usize]
+        );
+        assert_eq!(
+            storage.daily_leaderboard_configs().unwrap()[&LEADERBOARD].last_posted,
+            Some(
+                chrono_tz::EST
+                    .with_ymd_and_hms(year, 12, puzzle_count, 9, 0, 0)
+                    .unwrap()
+                    .with_timezone(&Utc)
+            )
+        );
+
+        // 9am in Sydney is the afternoon before in EST, so posts there start on the 2nd and
+        // run a day past the end, each with the puzzle released that morning
+        assert_eq!(sink.titles(SYDNEY_PUZZLE), puzzle_titles);
+
+        // A daily that can't be posted is turned off with a notice
+        assert_eq!(sink.titles(UNREGISTERED), vec!["❌  Error"]);
+        assert!(storage.daily_leaderboard_configs().unwrap()[&UNREGISTERED].is_disabled());
+
+        // The activity follows the days of the event
+        let mut activities = vec!["Waiting for Advent of Code".to_owned()];
+        activities.extend((1..=puzzle_count).map(|day| format!("Advent of Code Day {day}")));
+        activities.push("Waiting for Advent of Code".to_owned());
+        assert_eq!(*sink.activities.lock().unwrap(), activities);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_december() {
+        let (sink, storage) = simulate_december(2023).await;
+        assert_posted_every_day(&sink, &storage, 2023);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_december_with_12_puzzles() {
+        let (sink, storage) = simulate_december(2025).await;
+        assert_posted_every_day(&sink, &storage, 2025);
+    }
+
+    #[test]
+    fn test_missed_post() {
+        let tz = chrono_tz::EST;
+        let time = |day, hour, min| tz.with_ymd_and_hms(2023, 12, day, hour, min, 0).unwrap();
+        let schedule: Schedule = "0 0,9 * 12 *".parse().unwrap();
+        let last_posted = Some(time(2, 9, 0).with_timezone(&Utc));
+        let missed = |now: chrono::DateTime<_>| {
+            missed_post(&schedule, tz, last_posted, now.with_timezone(&Utc))
+                .map(|due| due.with_timezone(&tz))
+        };
+
+        // Restarting just after midnight catches up on the midnight post
+        assert_eq!(missed(time(3, 0, 20)), Some(time(3, 0, 0)));
+
+        // Only the latest missed post is sent
+        assert_eq!(missed(time(3, 9, 5)), Some(time(3, 9, 0)));
+
+        // Posts missed longer ago than the grace period are skipped
+        assert_eq!(missed(time(3, 8, 0)), None);
+
+        // Nothing is missed if it was already posted, or has never been posted
+        assert_eq!(missed(time(2, 10, 0)), None);
+        assert_eq!(
+            missed_post(&schedule, tz, None, time(3, 0, 20).with_timezone(&Utc)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_missed_first_post() {
+        let tz = chrono_tz::EST;
+        let time = |day, hour, min| tz.with_ymd_and_hms(2023, 12, day, hour, min, 0).unwrap();
+        let schedule = Schedule::at_hour(9);
+        // Set up just after the 2nd's post was due, so it was never posted
+        let registered = Some(time(2, 9, 10).with_timezone(&Utc));
+        let missed = |now: chrono::DateTime<_>| {
+            missed_post(&schedule, tz, registered, now.with_timezone(&Utc))
+                .map(|due| due.with_timezone(&tz))
+        };
+
+        // Posts that were due before it was set up aren't caught up on
+        assert_eq!(missed(time(2, 9, 20)), None);
+
+        // But the first post is, if the bot was offline when it was due
+        assert_eq!(missed(time(3, 9, 20)), Some(time(3, 9, 0)));
+    }
+
+    #[test]
+    fn test_missed_post_in_guild_timezone() {
+        // 9am in Sydney is 5pm the day before in EST
+        let tz = chrono_tz::Australia::Sydney;
+        let schedule = Schedule::at_hour(9);
+        let last_posted = tz.with_ymd_and_hms(2023, 12, 2, 9, 0, 0).unwrap();
+        let now = tz.with_ymd_and_hms(2023, 12, 3, 9, 30, 0).unwrap();
+        assert_eq!(
+            missed_post(
+                &schedule,
+                tz,
+                Some(last_posted.with_timezone(&Utc)),
+                now.with_timezone(&Utc)
+            ),
+            Some(
+                tz.with_ymd_and_hms(2023, 12, 3, 9, 0, 0)
+                    .unwrap()
+                    .with_timezone(&Utc)
+            )
+        );
     }
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use strum::IntoEnumIterator;
+
+use crate::{
+    aoc::{Leaderboard, LeaderboardMember},
+    config::LeaderboardOrdering,
+    ranking::rank_members,
+};
+
+/// A star earned by a member between two leaderboard snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewStar {
+    pub member_id: usize,
+    pub day: usize,
+    pub part: usize,
+    pub earned_at: DateTime<Utc>,
+}
+
+/// A member moving position under one of the leaderboard orderings (positions start at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankChange {
+    pub member_id: usize,
+    pub ordering: LeaderboardOrdering,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// How much a member's totals went up (or down) between two leaderboard snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreChange {
+    pub member_id: usize,
+    pub local_score: i64,
+    pub global_score: i64,
+    pub stars: i64,
+}
+
+/// Everything that changed between two snapshots of the same leaderboard
+#[derive(Debug, Default)]
+pub struct LeaderboardChanges {
+    /// Stars earned by members who were in both snapshots, oldest first
+    pub new_stars: Vec<NewStar>,
+
+    /// Members whose position changed, grouped by ordering
+    pub rank_changes: Vec<RankChange>,
+
+    /// Members whose scores or star counts changed
+    pub score_changes: Vec<ScoreChange>,
+
+    /// IDs of members only in the newer snapshot
+    pub joined: Vec<usize>,
+
+    /// IDs of members only in the older snapshot
+    pub left: Vec<usize>,
+}
+
+impl LeaderboardChanges {
+    /// Work out what changed from `before` to `after`
+    pub fn between(before: &Leaderboard, after: &Leaderboard) -> Self {
+        let before_members = members_by_id(before);
+        let after_members = members_by_id(after);
+
+        // Who joined and left
+        let mut joined: Vec<_> = after_members
+            .keys()
+            .filter(|id| !before_members.contains_key(id))
+            .copied()
+            .collect();
+        joined.sort();
+        let mut left: Vec<_> = before_members
+            .keys()
+            .filter(|id| !after_members.contains_key(id))
+            .copied()
+            .collect();
+        left.sort();
+
+        // Members in both snapshots, in a stable order
+        let mut remaining: Vec<_> = after_members
+            .iter()
+            .filter_map(|(id, after)| Some((*id, *before_members.get(id)?, *after)))
+            .collect();
+        remaining.sort_by_key(|(id, _, _)| *id);
+
+        // New stars and score changes. Stars held by members who just joined aren't included,
+        // as we can't tell whether they were earned before or after they joined.
+        let mut new_stars = Vec::new();
+        let mut score_changes = Vec::new();
+        for (id, before, after) in &remaining {
+            new_stars.extend(new_stars_for(before, after));
+
+            let change = ScoreChange {
+                member_id: *id,
+                local_score: after.local_score as i64 - before.local_score as i64,
+                global_score: after.global_score as i64 - before.global_score as i64,
+                stars: after.stars as i64 - before.stars as i64,
+            };
+            if change.local_score != 0 || change.global_score != 0 || change.stars != 0 {
+                score_changes.push(change);
+            }
+        }
+        new_stars.sort_by_key(|star| (star.earned_at, star.member_id, star.day, star.part));
+
+        // Rank movements under every ordering
+        let mut rank_changes = Vec::new();
+        for ordering in LeaderboardOrdering::iter() {
+            let before_positions = positions(before, ordering);
+            let after_positions = positions(after, ordering);
+            for (id, _, _) in &remaining {
+                let (from, to) = (before_positions[id], after_positions[id]);
+                if from != to {
+                    rank_changes.push(RankChange {
+                        member_id: *id,
+                        ordering,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+
+        Self {
+            new_stars,
+            rank_changes,
+            score_changes,
+            joined,
+            left,
+        }
+    }
+
+    /// Whether nothing changed at all
+    pub fn is_empty(&self) -> bool {
+        self.new_stars.is_empty()
+            && self.rank_changes.is_empty()
+            && self.score_changes.is_empty()
+            && self.joined.is_empty()
+            && self.left.is_empty()
+    }
+}
+
+fn members_by_id(leaderboard: &Leaderboard) -> HashMap<usize, &LeaderboardMember> {
+    leaderboard
+        .members
+        .values()
+        .map(|member| (member.id, member))
+        .collect()
+}
+
+fn positions(leaderboard: &Leaderboard, ordering: LeaderboardOrdering) -> HashMap<usize, usize> {
+    rank_members(leaderboard, ordering)
+        .into_iter()
+        .enumerate()
+        .map(|(i, ranked)| (ranked.member.id, i + 1))
+        .collect()
+}
+
+fn new_stars_for(before: &LeaderboardMember, after: &LeaderboardMember) -> Vec<NewStar> {
+    let mut stars = Vec::new();
+    for (day, parts) in &after.completion_day_level {
+        for (part, entry) in parts {
+            let already_earned = before
+                .completion_day_level
+                .get(day)
+                .is_some_and(|parts| parts.contains_key(part));
+            if already_earned {
+                continue;
+            }
+
+            stars.push(NewStar {
+                member_id: after.id,
+                day: *day,
+                part: *part,
+                earned_at: entry.earned_at(),
+            });
+        }
+    }
+    stars
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::{LeaderboardChanges, NewStar, RankChange, ScoreChange};
+    use crate::{aoc::Leaderboard, config::LeaderboardOrdering};
+
+    fn fixture(json: &str) -> Leaderboard {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn before() -> Leaderboard {
+        fixture(include_str!("../fixtures/leaderboard_2023.json"))
+    }
+
+    fn after() -> Leaderboard {
+        fixture(include_str!("../fixtures/leaderboard_2023_later.json"))
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let changes = LeaderboardChanges::between(&before(), &before());
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_joined_and_left() {
+        let changes = LeaderboardChanges::between(&before(), &after());
+        assert_eq!(changes.joined, vec![105]);
+        assert_eq!(changes.left, vec![104]);
+    }
+
+    #[test]
+    fn test_new_stars() {
+        let changes = LeaderboardChanges::between(&before(), &after());
+        let star = |member_id, day, part, ts| NewStar {
+            member_id,
+            day,
+            part,
+            earned_at: Utc.timestamp_opt(ts, 0).unwrap(),
+        };
+        assert_eq!(
+            changes.new_stars,
+            vec![
+                star(103, 1, 2, 1701420000),
+                star(102, 2, 2, 1701600000),
+                star(102, 3, 1, 1701601000),
+                star(102, 3, 2, 1701602800),
+                star(102, 4, 1, 1701666600),
+                star(102, 4, 2, 1701667200),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_score_changes() {
+        let changes = LeaderboardChanges::between(&before(), &after());
+        assert_eq!(
+            changes.score_changes,
+            vec![
+                ScoreChange {
+                    member_id: 102,
+                    local_score: 17,
+                    global_score: 0,
+                    stars: 5,
+                },
+                ScoreChange {
+                    member_id: 103,
+                    local_score: 2,
+                    global_score: 0,
+                    stars: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rank_changes() {
+        let changes = LeaderboardChanges::between(&before(), &after());
+        let local_score_changes: Vec<_> = changes
+            .rank_changes
+            .iter()
+            .filter(|change| change.ordering == LeaderboardOrdering::LocalScore)
+            .cloned()
+            .collect();
+        assert_eq!(
+            local_score_changes,
+            vec![
+                RankChange {
+                    member_id: 101,
+                    ordering: LeaderboardOrdering::LocalScore,
+                    from: 1,
+                    to: 2,
+                },
+                RankChange {
+                    member_id: 102,
+                    ordering: LeaderboardOrdering::LocalScore,
+                    from: 2,
+                    to: 1,
+                },
+            ]
+        );
+
+        // Bob also overtakes Alice on stars
+        assert!(changes.rank_changes.contains(&RankChange {
+            member_id: 102,
+            ordering: LeaderboardOrdering::Stars,
+            from: 2,
+            to: 1,
+        }));
+    }
+}
@@ -1,11 +1,18 @@
 use std::fmt::Write;
 use std::sync::Arc;
 
+use chrono::Duration;
 use serenity::builder::CreateEmbed;
 
 use crate::{
-    aoc::{Leaderboard, LeaderboardCacheEntry, PuzzleDetails},
+    aoc::{
+        Leaderboard, LeaderboardCacheEntry, LeaderboardMember, PuzzleDetails, COMBINED_ID_SEPARATOR,
+    },
     config::LeaderboardOrdering,
+    diff::LeaderboardChanges,
+    links::MemberNames,
+    ranking::{rank_day_solve_times, rank_members},
+    scoring::verify_local_scores,
 };
 
 pub const EMBED_COLOR: i32 = 0xFFFE60;
@@ -26,12 +33,14 @@ macro_rules! trunc {
 pub fn make_leaderboard_embed(
     leaderboard: Arc<LeaderboardCacheEntry>,
     ordering: LeaderboardOrdering,
+    names: &MemberNames,
 ) -> CreateEmbed {
     CreateEmbed::default()
         .title("🏆  Leaderboard")
         .description(leaderboard_embed_content(
             &leaderboard.leaderboard,
             ordering,
+            names,
         ))
         .timestamp(leaderboard.created_at.to_rfc3339())
         .url(generate_leaderboard_url(
@@ -43,23 +52,50 @@ pub fn make_leaderboard_embed(
         .to_owned()
 }
 
+/// The name to show for a member, anonymous users don't have one
+pub fn member_name(member: &LeaderboardMember) -> String {
+    member
+        .name
+        .to_owned()
+        .unwrap_or(format!("Anon #{}", member.id))
+}
+
+/// The name to show for a member, preferring the display name of a linked discord user
+pub fn display_name(member: &LeaderboardMember, names: &MemberNames) -> String {
+    names
+        .get(&member.id)
+        .cloned()
+        .unwrap_or_else(|| member_name(member))
+}
+
+/// Format a duration like `14m32s`, `2h05m10s` or `3d 4h02m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (days, hours, mins, secs) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    match (days, hours) {
+        (0, 0) => format!("{mins}m{secs:02}s"),
+        (0, _) => format!("{hours}h{mins:02}m{secs:02}s"),
+        _ => format!("{days}d {hours}h{mins:02}m"),
+    }
+}
+
 pub fn leaderboard_embed_content(
     leaderboard: &Leaderboard,
     ordering: LeaderboardOrdering,
+    names: &MemberNames,
 ) -> String {
-    // Collect member entries
-    let mut members: Vec<_> = leaderboard.members.values().collect();
+    // Collect member entries in order
+    let members = rank_members(leaderboard, ordering);
 
     // Get longest name
     let longest_name_len = members
         .iter()
-        .map(|member| {
-            member
-                .name
-                .to_owned()
-                .unwrap_or(format!("Anon #{}", member.id))
-                .len()
-        })
+        .map(|ranked| display_name(ranked.member, names).len())
         .max()
         .unwrap_or(MAX_NAME_LENGTH)
         .min(MAX_NAME_LENGTH);
@@ -67,35 +103,14 @@ pub fn leaderboard_embed_content(
     // Get longest score
     let longest_score_len = members
         .iter()
-        .map(|member| match ordering {
-            LeaderboardOrdering::LocalScore => member.local_score.to_string().len(),
-            LeaderboardOrdering::GlobalScore => member.global_score.to_string().len(),
-            LeaderboardOrdering::Stars => member.stars.to_string().len(),
-        })
+        .map(|ranked| ranked.score.to_string().len())
         .max()
         .unwrap_or(2);
 
-    // Sort them
-    // TODO: sort_by should be stable, but appears to reorder equal elements?
-    members.sort_by(|a, b| match ordering {
-        // Local score (default)
-        LeaderboardOrdering::LocalScore => b.local_score.cmp(&a.local_score),
-        // Global score (ties broken by local score)
-        LeaderboardOrdering::GlobalScore => match b.global_score.cmp(&a.global_score) {
-            std::cmp::Ordering::Equal => b.local_score.cmp(&a.local_score),
-            x => x,
-        },
-        // Stars (ties broken by who got the most recent star first)
-        LeaderboardOrdering::Stars => match b.stars.cmp(&a.stars) {
-            std::cmp::Ordering::Equal => a.last_star_ts.cmp(&b.last_star_ts),
-            x => x,
-        },
-    });
-
     let content: String = members
         .iter()
         .enumerate()
-        .fold(String::new(), |mut out, (i, member)| {
+        .fold(String::new(), |mut out, (i, ranked)| {
             let _ = writeln!(
                 out,
                 "{}: {}  {} {}",
@@ -110,26 +125,16 @@ pub fn leaderboard_embed_content(
                 ),
                 format_args!(
                     "{:width$}",
-                    trunc!(
-                        member
-                            .name
-                            .to_owned()
-                            .unwrap_or(format!("Anon #{}", member.id)),
-                        MAX_NAME_LENGTH
-                    ),
+                    trunc!(display_name(ranked.member, names), MAX_NAME_LENGTH),
                     width = longest_name_len,
                 ),
-                format_args!(
-                    "{: >width$}",
-                    match ordering {
-                        LeaderboardOrdering::LocalScore => member.local_score,
-                        LeaderboardOrdering::GlobalScore => member.global_score,
-                        LeaderboardOrdering::Stars => member.stars,
-                    },
-                    width = longest_score_len,
-                ),
+                format_args!("{: >width$}", ranked.score, width = longest_score_len,),
                 match ordering {
-                    LeaderboardOrdering::Stars => "⭐️",
+                    LeaderboardOrdering::Stars | LeaderboardOrdering::StarsThenLastStar => "⭐️",
+                    LeaderboardOrdering::MedianSolveTime
+                    | LeaderboardOrdering::TotalSolveTime
+                    | LeaderboardOrdering::AveragePartDelta => "⏱️",
+                    LeaderboardOrdering::CurrentStreak => "🔥",
                     _ => "💎",
                 },
             );
@@ -139,6 +144,238 @@ pub fn leaderboard_embed_content(
     format!("```js\n{}```", content)
 }
 
+pub fn make_day_leaderboard_embed(
+    leaderboard: Arc<LeaderboardCacheEntry>,
+    day: usize,
+    names: &MemberNames,
+) -> CreateEmbed {
+    CreateEmbed::default()
+        .title(format!("⏱️  Day {day} Solve Times"))
+        .description(day_leaderboard_embed_content(
+            &leaderboard.leaderboard,
+            day,
+            names,
+        ))
+        .timestamp(leaderboard.created_at.to_rfc3339())
+        .url(generate_leaderboard_url(
+            &leaderboard.leaderboard.event,
+            &leaderboard.leaderboard_id,
+        ))
+        .color(EMBED_COLOR)
+        .footer(|f| {
+            f.text(format!(
+                "Year {}, timed from when the puzzle unlocked",
+                leaderboard.leaderboard.event
+            ))
+        })
+        .to_owned()
+}
+
+pub fn day_leaderboard_embed_content(
+    leaderboard: &Leaderboard,
+    day: usize,
+    names: &MemberNames,
+) -> String {
+    let solve_times = rank_day_solve_times(leaderboard, day);
+    if solve_times.is_empty() {
+        return format!("Nobody has solved day {day} yet 🫙");
+    }
+
+    // Format each column up front so they can be aligned
+    let rows: Vec<_> = solve_times
+        .iter()
+        .map(|times| {
+            (
+                trunc!(display_name(times.member, names), MAX_NAME_LENGTH),
+                times.part1.map(format_duration).unwrap_or_default(),
+                times.part2.map(format_duration).unwrap_or_default(),
+                times
+                    .delta()
+                    .map(|delta| format!("(+{})", format_duration(delta)))
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+    let name_len = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let part1_len = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(6);
+    let part2_len = rows.iter().map(|row| row.2.len()).max().unwrap_or(0).max(6);
+    let rank_len = rows.len().to_string().len();
+
+    let mut content = format!(
+        "{: <rank_len$}  {: <name_len$}  {: >part1_len$}  {: >part2_len$}\n",
+        "", "", "Part 1", "Part 2",
+    );
+    for (i, (name, part1, part2, delta)) in rows.iter().enumerate() {
+        let _ = writeln!(
+            content,
+            "{:0>rank_len$}: {: <name_len$}  {: >part1_len$}  {: >part2_len$}  {}",
+            i + 1,
+            name,
+            part1,
+            part2,
+            delta,
+        );
+    }
+
+    format!("```js\n{}```", content)
+}
+
+/// Longest list of member changes to show, to stay within the embed description limit
+const MAX_CHANGE_LINES: usize = 30;
+
+pub fn make_changes_embed(
+    before: &LeaderboardCacheEntry,
+    after: Arc<LeaderboardCacheEntry>,
+    ordering: LeaderboardOrdering,
+    names: &MemberNames,
+) -> CreateEmbed {
+    let changes = LeaderboardChanges::between(&before.leaderboard, &after.leaderboard);
+    CreateEmbed::default()
+        .title("📈  Leaderboard Changes")
+        .description(format!(
+            "Since <t:{}:R>\n\n{}",
+            before.created_at.timestamp(),
+            changes_embed_content(
+                &changes,
+                &before.leaderboard,
+                &after.leaderboard,
+                ordering,
+                names
+            )
+        ))
+        .timestamp(after.created_at.to_rfc3339())
+        .url(generate_leaderboard_url(
+            &after.leaderboard.event,
+            &after.leaderboard_id,
+        ))
+        .color(EMBED_COLOR)
+        .footer(|f| f.text(format!("Year {}", after.leaderboard.event)))
+        .to_owned()
+}
+
+/// One line per member whose score or position changed, e.g. "**Alice** +3 ⭐ +120 💎, #5 → #2",
+/// followed by who joined and left
+pub fn changes_embed_content(
+    changes: &LeaderboardChanges,
+    before: &Leaderboard,
+    after: &Leaderboard,
+    ordering: LeaderboardOrdering,
+    names: &MemberNames,
+) -> String {
+    if changes.is_empty() {
+        return "Nothing has changed 💤".to_owned();
+    }
+    let name = |member_id: usize| {
+        after
+            .members
+            .values()
+            .chain(before.members.values())
+            .find(|member| member.id == member_id)
+            .map(|member| display_name(member, names))
+            .unwrap_or_else(|| format!("Anon #{member_id}"))
+    };
+
+    // Members whose totals changed, most stars first, then anyone who only moved
+    let mut scores: Vec<_> = changes.score_changes.iter().collect();
+    scores.sort_by_key(|change| (-change.stars, -change.local_score, change.member_id));
+    let mut member_ids: Vec<_> = scores.iter().map(|change| change.member_id).collect();
+    for rank in changes.rank_changes.iter() {
+        if rank.ordering == ordering && !member_ids.contains(&rank.member_id) {
+            member_ids.push(rank.member_id);
+        }
+    }
+
+    let mut lines: Vec<_> = member_ids
+        .iter()
+        .take(MAX_CHANGE_LINES)
+        .map(|member_id| {
+            let mut line = format!("**{}**", name(*member_id));
+            if let Some(score) = scores.iter().find(|s| s.member_id == *member_id) {
+                if score.stars != 0 {
+                    let _ = write!(line, " {:+} ⭐", score.stars);
+                }
+                if score.local_score != 0 {
+                    let _ = write!(line, " {:+} 💎", score.local_score);
+                }
+            }
+            let rank = changes
+                .rank_changes
+                .iter()
+                .find(|rank| rank.ordering == ordering && rank.member_id == *member_id);
+            if let Some(rank) = rank {
+                let _ = write!(line, ", #{} → #{}", rank.from, rank.to);
+            }
+            line
+        })
+        .collect();
+    if member_ids.len() > MAX_CHANGE_LINES {
+        lines.push(format!(
+            "...and {} more",
+            member_ids.len() - MAX_CHANGE_LINES
+        ));
+    }
+
+    // Who came and went
+    let list = |ids: &[usize]| {
+        ids.iter()
+            .map(|id| name(*id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !changes.joined.is_empty() {
+        lines.push(format!("👋 Joined: {}", list(&changes.joined)));
+    }
+    if !changes.left.is_empty() {
+        lines.push(format!("🚪 Left: {}", list(&changes.left)));
+    }
+    lines.join("\n")
+}
+
+/// Longest list of score mismatches to show, to stay within the embed description limit
+const MAX_MISMATCH_LINES: usize = 30;
+
+pub fn make_score_verification_embed(leaderboard: &Leaderboard) -> CreateEmbed {
+    let mismatches = verify_local_scores(leaderboard);
+    let description = if mismatches.is_empty() {
+        "✅ Every local score matches the one computed from star times".to_owned()
+    } else {
+        let mut description =
+            mismatches
+                .iter()
+                .take(MAX_MISMATCH_LINES)
+                .fold(String::new(), |mut out, mismatch| {
+                    let name = leaderboard
+                        .members
+                        .values()
+                        .find(|member| member.id == mismatch.member_id)
+                        .map(member_name)
+                        .unwrap_or_default();
+                    let _ = writeln!(
+                        out,
+                        "❌ **{}**: AoC says {}, computed {}",
+                        trunc!(name, MAX_NAME_LENGTH),
+                        mismatch.reported,
+                        mismatch.computed
+                    );
+                    out
+                });
+        if mismatches.len() > MAX_MISMATCH_LINES {
+            let _ = writeln!(
+                description,
+                "...and {} more",
+                mismatches.len() - MAX_MISMATCH_LINES
+            );
+        }
+        description
+    };
+
+    CreateEmbed::default()
+        .title("🔍  Score Verification")
+        .description(description)
+        .color(EMBED_COLOR)
+        .to_owned()
+}
+
 pub fn make_puzzle_embed(
     year: usize,
     day: usize,
@@ -180,7 +417,9 @@ pub fn make_message_embed(reason: ResponseReason, message: &str) -> CreateEmbed
         .to_owned()
 }
 
+/// Combined leaderboards link to the first leaderboard they combine
 pub fn generate_leaderboard_url(year: &str, id: &str) -> String {
+    let id = id.split(COMBINED_ID_SEPARATOR).next().unwrap_or(id);
     format!(
         "https://adventofcode.com/{}/leaderboard/private/view/{}",
         year, id
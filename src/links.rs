@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+
+use serenity::{
+    http::{Http, StatusCode},
+    model::prelude::{GuildId, Member, UserId},
+    prelude::Context,
+};
+
+use crate::storage::Storage;
+
+/// Names to show instead of AoC names, keyed by AoC member ID
+pub type MemberNames = HashMap<usize, String>;
+
+/// The most guild members Discord will list at once
+const MEMBER_PAGE_SIZE: u64 = 1000;
+
+/// Above this many users, the guild's members are listed instead of fetching each user
+const MAX_MEMBER_LOOKUPS: usize = 25;
+
+/// Whether a member can be linked to a user, given who it's linked to now. Anyone can claim a
+/// member nobody else has, but only admins can take a member away from another user.
+pub fn can_link(current: Option<UserId>, user_id: UserId, is_admin: bool) -> bool {
+    is_admin || current.is_none_or(|current| current == user_id)
+}
+
+/// Get the discord display names of every AoC member linked to a user in a guild. Links are
+/// left out if they can't be loaded, so AoC names are shown instead.
+pub async fn linked_member_names(
+    ctx: &Context,
+    storage: &dyn Storage,
+    guild_id: GuildId,
+) -> MemberNames {
+    let links = match storage.member_links(guild_id) {
+        Ok(links) => links,
+        Err(error) => {
+            log::error!("Failed to load member links for {guild_id}: {error}");
+            return MemberNames::new();
+        }
+    };
+    if links.is_empty() {
+        return MemberNames::new();
+    }
+
+    let user_ids = links.values().copied().collect();
+    let members = match fetch_members(&ctx.http, guild_id, &user_ids).await {
+        Ok(members) => members,
+        Err(error) => {
+            log::warn!("Failed to get linked users in {guild_id}: {error}");
+            return MemberNames::new();
+        }
+    };
+    links
+        .into_iter()
+        .filter_map(|(member_id, user_id)| {
+            let member = members.get(&user_id)?;
+            Some((member_id, member.display_name().into_owned()))
+        })
+        .collect()
+}
+
+/// Get the guild members for a set of users, leaving out any who have left the guild. They're
+/// fetched one at a time, unless there are so many that listing the whole guild is quicker.
+pub async fn fetch_members(
+    http: &Http,
+    guild_id: GuildId,
+    user_ids: &HashSet<UserId>,
+) -> serenity::Result<HashMap<UserId, Member>> {
+    // Listing members needs the privileged Server Members intent, which most bots don't have
+    if user_ids.len() > MAX_MEMBER_LOOKUPS {
+        match list_members(http, guild_id, user_ids).await {
+            Err(error) if status_code(&error) == Some(StatusCode::FORBIDDEN) => {
+                log::info!("Not allowed to list the members of {guild_id}, getting linked users one at a time");
+            }
+            result => return result,
+        }
+    }
+
+    let mut found = HashMap::new();
+    for user_id in user_ids {
+        match guild_id.member(http, *user_id).await {
+            Ok(member) => {
+                found.insert(*user_id, member);
+            }
+            Err(error) if status_code(&error) == Some(StatusCode::NOT_FOUND) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(found)
+}
+
+/// Find a set of users by listing the guild's members a page at a time, stopping once every
+/// user has been found
+async fn list_members(
+    http: &Http,
+    guild_id: GuildId,
+    user_ids: &HashSet<UserId>,
+) -> serenity::Result<HashMap<UserId, Member>> {
+    let mut found = HashMap::new();
+    let mut after = None;
+    while found.len() < user_ids.len() {
+        let page = guild_id
+            .members(http, Some(MEMBER_PAGE_SIZE), after)
+            .await?;
+        let is_last_page = page.len() < MEMBER_PAGE_SIZE as usize;
+        after = page.last().map(|member| member.user.id);
+        found.extend(
+            page.into_iter()
+                .filter(|member| user_ids.contains(&member.user.id))
+                .map(|member| (member.user.id, member)),
+        );
+        if is_last_page {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+/// The status Discord responded with, if a request was unsuccessful
+fn status_code(error: &serenity::Error) -> Option<StatusCode> {
+    match error {
+        serenity::Error::Http(error) => error.status_code(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::prelude::UserId;
+
+    use super::can_link;
+
+    #[test]
+    fn test_only_admins_can_take_linked_members() {
+        // Anyone can claim an unlinked member, or relink their own
+        assert!(can_link(None, UserId(1), false));
+        assert!(can_link(Some(UserId(1)), UserId(1), false));
+
+        // But a member linked to someone else stays theirs
+        assert!(!can_link(Some(UserId(2)), UserId(1), false));
+        assert!(can_link(Some(UserId(2)), UserId(1), true));
+    }
+}
@@ -1,14 +1,26 @@
 mod aoc;
 mod bot;
+mod calendar;
+mod clock;
 mod commands;
 mod config;
 mod daily;
+mod diff;
 mod format;
+mod links;
+mod notify;
+mod prefetch;
+mod ranking;
+mod rewards;
+mod schedule;
+mod scoring;
+mod storage;
 
 use bot::Bot;
 use dotenv::dotenv;
 use fern::colors::{Color, ColoredLevelConfig};
 use std::env;
+use storage::TokenCipher;
 
 #[tokio::main]
 async fn main() {
@@ -17,6 +29,28 @@ async fn main() {
     // Load env from .env
     dotenv().ok();
 
+    // Handle command line modes
+    match env::args().nth(1).as_deref() {
+        Some("generate-key") => {
+            println!("{}", TokenCipher::generate_key());
+            return;
+        }
+        Some("rekey") => {
+            match storage::rekey_from_env() {
+                Ok(count) => println!(
+                    "Re-encrypted {count} tokens, replace TOKEN_KEY with NEW_TOKEN_KEY before restarting the bot"
+                ),
+                Err(err) => eprintln!("Failed to re-encrypt tokens, nothing was changed: {err}"),
+            }
+            return;
+        }
+        Some(mode) => {
+            eprintln!("Unknown mode `{mode}`, expected `generate-key` or `rekey`");
+            return;
+        }
+        None => {}
+    }
+
     // Get the token from the env
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
 
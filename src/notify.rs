@@ -0,0 +1,254 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{Datelike, Utc};
+use chrono_tz::Tz;
+use serenity::{model::prelude::ChannelId, prelude::Context};
+
+use crate::{
+    aoc::{AOCData, Leaderboard, LeaderboardCacheEntry},
+    calendar::{est, EventCalendar},
+    daily::guild_timezone,
+    diff::{LeaderboardChanges, NewStar},
+    format::{display_name, format_duration},
+    links::{linked_member_names, MemberNames},
+    storage::SharedStorage,
+};
+
+/// AoC asks that private leaderboards aren't polled more than once every 15 minutes
+const POLL_INTERVAL_SECS: u64 = 900;
+
+/// Snapshots older than this aren't used to announce stars earned while the bot was offline
+const MAX_BASELINE_AGE_SECS: i64 = 60 * 60;
+
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+pub async fn star_notifications(aoc_data: Arc<AOCData>, storage: SharedStorage, ctx: Context) {
+    // Create EST timezone
+    let tz = est();
+
+    // The last leaderboard each channel was notified about
+    let mut last_seen: HashMap<ChannelId, Arc<LeaderboardCacheEntry>> = HashMap::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+
+        // Stars can only be earned during December
+        let time = Utc::now().with_timezone(&tz);
+        if time.month() != 12 {
+            last_seen.clear();
+            continue;
+        }
+        let year = EventCalendar::current(time.with_timezone(&Utc)).year();
+
+        // Get config, forgetting about channels that no longer want notifications
+        let star_notify_configs = match storage.star_notify_configs() {
+            Ok(configs) => configs,
+            Err(error) => {
+                log::error!("Failed to get star notification configs: {error}");
+                continue;
+            }
+        };
+        last_seen.retain(|channel_id, _| star_notify_configs.contains_key(channel_id));
+        log::info!(
+            "Checking {} leaderboards for new stars",
+            star_notify_configs.len()
+        );
+
+        for (channel_id, notify_config) in &star_notify_configs {
+            let guild_config = match storage
+                .guild_config(notify_config.guild_id, notify_config.board.as_deref())
+            {
+                Ok(Some(guild_config)) => guild_config,
+                Ok(None) => {
+                    log::warn!(
+                        "Star notifications in {channel_id} are for a leaderboard that isn't registered"
+                    );
+                    continue;
+                }
+                Err(error) => {
+                    log::error!(
+                        "Failed to get config of guild {} for star notifications: {error}",
+                        notify_config.guild_id
+                    );
+                    continue;
+                }
+            };
+
+            // Get the leaderboard, and what it looked like last time we checked
+            let previous = last_seen
+                .get(channel_id)
+                .cloned()
+                .or_else(|| recent_snapshot(&aoc_data, year, &guild_config.leaderboard_ids()));
+            let leaderboard = aoc_data
+                .get_combined_leaderboard(
+                    &year.to_string(),
+                    &guild_config.leaderboard_ids(),
+                    &guild_config.auth,
+                    false,
+                )
+                .await;
+            let leaderboard = match leaderboard {
+                Ok(leaderboard) => leaderboard,
+                Err(error) => {
+                    log::warn!(
+                        "Failed to get leaderboard for star notifications in {channel_id}: {error}"
+                    );
+                    continue;
+                }
+            };
+            last_seen.insert(*channel_id, leaderboard.clone());
+
+            // Nothing to compare against the first time we see a leaderboard
+            let Some(previous) = previous.filter(|previous| {
+                previous.leaderboard_id == leaderboard.leaderboard_id
+                    && previous.leaderboard.event == leaderboard.leaderboard.event
+            }) else {
+                continue;
+            };
+
+            // Announce new stars
+            let changes =
+                LeaderboardChanges::between(&previous.leaderboard, &leaderboard.leaderboard);
+            if changes.new_stars.is_empty() {
+                continue;
+            }
+            let names = linked_member_names(&ctx, &*storage, notify_config.guild_id).await;
+            let timezone = guild_timezone(&*storage, notify_config.guild_id);
+            let lines = star_lines(
+                &leaderboard.leaderboard,
+                year,
+                &changes.new_stars,
+                &names,
+                timezone,
+            );
+            for message in batch_lines(&lines) {
+                if let Err(error) = channel_id.say(&ctx.http, message).await {
+                    log::warn!("Failed to send star notification to {channel_id}: {error}");
+                }
+            }
+        }
+    }
+}
+
+/// The latest stored snapshot of a leaderboard, combining several if needed, if it was taken
+/// recently
+fn recent_snapshot(
+    aoc_data: &AOCData,
+    year: i32,
+    leaderboard_ids: &[&str],
+) -> Option<Arc<LeaderboardCacheEntry>> {
+    let since = Utc::now() - chrono::Duration::seconds(MAX_BASELINE_AGE_SECS);
+    let snapshots = aoc_data
+        .history()?
+        .query_combined(&year.to_string(), leaderboard_ids, since..);
+    match snapshots {
+        Ok(mut snapshots) => snapshots.pop().map(Arc::new),
+        Err(error) => {
+            log::warn!("Failed to get leaderboard snapshots for star notifications: {error}");
+            None
+        }
+    }
+}
+
+/// One line per member and day, e.g. "Alice earned ⭐⭐ on Day 7 at 00:14 EST (part 2 in 14m32s)"
+fn star_lines(
+    leaderboard: &Leaderboard,
+    year: i32,
+    new_stars: &[NewStar],
+    names: &MemberNames,
+    timezone: Tz,
+) -> Vec<String> {
+    // Only mention the latest part each member solved for a day
+    let mut latest: Vec<&NewStar> = Vec::new();
+    for star in new_stars {
+        match latest
+            .iter_mut()
+            .find(|other| other.member_id == star.member_id && other.day == star.day)
+        {
+            Some(other) if star.part > other.part => *other = star,
+            Some(_) => {}
+            None => latest.push(star),
+        }
+    }
+
+    latest
+        .into_iter()
+        .filter_map(|star| {
+            let member = leaderboard
+                .members
+                .values()
+                .find(|member| member.id == star.member_id)?;
+            let stars_on_day = member
+                .completion_day_level
+                .get(&star.day)
+                .map_or(0, |parts| parts.len());
+            let solve_time = EventCalendar::new(year)
+                .unlock_time(star.day as u32)
+                .map(|unlock| format!(" in {}", format_duration(star.earned_at - unlock)))
+                .unwrap_or_default();
+            Some(format!(
+                "{} earned {} on Day {} at {} (part {}{})",
+                display_name(member, names),
+                "⭐".repeat(stars_on_day),
+                star.day,
+                star.earned_at.with_timezone(&timezone).format("%H:%M %Z"),
+                star.part,
+                solve_time,
+            ))
+        })
+        .collect()
+}
+
+/// Join lines into as few messages as will fit in discord's length limit
+fn batch_lines(lines: &[String]) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for line in lines {
+        match messages.last_mut() {
+            Some(message) if message.len() + line.len() < MAX_MESSAGE_LENGTH => {
+                message.push('\n');
+                message.push_str(line);
+            }
+            _ => messages.push(line.clone()),
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod test {
+    use super::star_lines;
+    use crate::{aoc::Leaderboard, diff::LeaderboardChanges, links::MemberNames};
+
+    #[test]
+    fn test_star_lines() {
+        let before: Leaderboard =
+            serde_json::from_str(include_str!("../fixtures/leaderboard_2023.json")).unwrap();
+        let after: Leaderboard =
+            serde_json::from_str(include_str!("../fixtures/leaderboard_2023_later.json")).unwrap();
+        let changes = LeaderboardChanges::between(&before, &after);
+
+        // Linked members are shown by their discord name
+        let names = MemberNames::from([(102, "Robert".to_owned())]);
+        assert_eq!(
+            star_lines(&after, 2023, &changes.new_stars, &names, chrono_tz::EST),
+            vec![
+                "Anon #103 earned ⭐⭐ on Day 1 at 03:40 EST (part 2 in 3h40m00s)",
+                "Robert earned ⭐⭐ on Day 2 at 05:40 EST (part 2 in 1d 5h40m)",
+                "Robert earned ⭐⭐ on Day 3 at 06:26 EST (part 2 in 6h26m40s)",
+                "Robert earned ⭐⭐ on Day 4 at 00:20 EST (part 2 in 20m00s)",
+            ]
+        );
+
+        // Times are shown in the guild's timezone
+        assert_eq!(
+            star_lines(
+                &after,
+                2023,
+                &changes.new_stars,
+                &names,
+                chrono_tz::Australia::Sydney
+            )[0],
+            "Anon #103 earned ⭐⭐ on Day 1 at 19:40 AEDT (part 2 in 3h40m00s)"
+        );
+    }
+}
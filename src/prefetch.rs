@@ -0,0 +1,273 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, Utc};
+use serenity::model::prelude::GuildId;
+
+use crate::{
+    aoc::{AOCData, AocApi, LeaderboardAuth, MIN_LEADERBOARD_INTERVAL_SECS},
+    calendar::{est, EventCalendar},
+    storage::{SharedStorage, Storage},
+};
+
+/// How often leaderboards are refreshed during December. A little longer than AoC's minimum
+/// interval, so the cached copy has always expired by the time it's refreshed.
+const PREFETCH_INTERVAL_SECS: u64 = MIN_LEADERBOARD_INTERVAL_SECS + 30;
+
+/// How often leaderboards are refreshed during the rest of the year, when they rarely change.
+/// Short enough that commands can still be answered from a slightly stale copy.
+const OFF_SEASON_PREFETCH_INTERVAL_SECS: u64 = 50 * 60;
+
+/// How many times in a row credentials can be rejected for a leaderboard before they're no
+/// longer used to prefetch it. They're tried again once the guild registers them again.
+const MAX_PREFETCH_REJECTIONS: u32 = 3;
+
+/// Credentials that should be able to fetch a leaderboard, with every registration using them
+/// and how many times in a row each of those has had them rejected
+struct Candidate {
+    auth: LeaderboardAuth,
+    registrations: Vec<(GuildId, String, u32)>,
+}
+
+impl Candidate {
+    /// Registering again resets a registration's count, so the credentials get another chance
+    /// if any guild using them does
+    fn rejections(&self) -> u32 {
+        self.registrations
+            .iter()
+            .map(|(_, _, rejections)| *rejections)
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Keep every registered leaderboard for the latest event in the cache, so commands can be
+/// answered without waiting on AoC
+pub async fn prefetch_leaderboards(aoc_data: Arc<AOCData>, storage: SharedStorage) {
+    loop {
+        let now = Utc::now();
+        let year = EventCalendar::current(now).year();
+        match prefetch(&aoc_data, &*storage, year).await {
+            Ok(count) => log::info!("Prefetched {count} leaderboards for {year}"),
+            Err(err) => log::error!("Failed to prefetch leaderboards: {err}"),
+        }
+        tokio::time::sleep(prefetch_interval(now)).await;
+    }
+}
+
+/// Refresh every registered leaderboard for an event, returning how many are now cached.
+/// Leaderboards registered by several guilds, or combined with others, are only fetched once,
+/// unless AoC rejects one guild's credentials and the next guild's have to be tried.
+/// Rejections are saved in storage, so credentials that keep failing stay skipped after a restart.
+async fn prefetch<A: AocApi + 'static>(
+    aoc_data: &AOCData<A>,
+    storage: &dyn Storage,
+    year: i32,
+) -> Result<usize, String> {
+    // Find every leaderboard, along with every guild's credentials that should be able to fetch it
+    let rejections = storage.prefetch_rejections().map_err(|e| e.to_string())?;
+    let mut leaderboards: HashMap<String, Vec<Candidate>> = HashMap::new();
+    for (guild_id, guild_config) in storage.all_guild_configs().map_err(|e| e.to_string())? {
+        for leaderboard_id in guild_config.leaderboard_ids() {
+            let key = (
+                guild_id,
+                guild_config.name.clone(),
+                leaderboard_id.to_owned(),
+            );
+            let registration = (
+                guild_id,
+                guild_config.name.clone(),
+                rejections.get(&key).copied().unwrap_or_default(),
+            );
+            let candidates = leaderboards.entry(leaderboard_id.to_owned()).or_default();
+            match candidates
+                .iter_mut()
+                .find(|candidate| candidate.auth == guild_config.auth)
+            {
+                Some(candidate) => candidate.registrations.push(registration),
+                None => candidates.push(Candidate {
+                    auth: guild_config.auth.clone(),
+                    registrations: vec![registration],
+                }),
+            }
+        }
+    }
+
+    // Fetch them one at a time, the request governor paces them if there are many
+    let event_id = year.to_string();
+    let mut count = 0;
+    for (leaderboard_id, mut candidates) in leaderboards {
+        // Skip credentials that keep being rejected, so AoC isn't asked with them forever, and
+        // try the ones rejected the fewest times in a row first
+        candidates.retain(|candidate| candidate.rejections() < MAX_PREFETCH_REJECTIONS);
+        candidates.sort_by_key(Candidate::rejections);
+        if candidates.is_empty() {
+            log::info!(
+                "Not prefetching leaderboard {leaderboard_id}, its credentials keep being rejected"
+            );
+            continue;
+        }
+
+        // Try each guild's credentials until one works
+        for candidate in candidates {
+            match aoc_data
+                .refresh_leaderboard(&event_id, &leaderboard_id, &candidate.auth)
+                .await
+            {
+                Ok(_) => {
+                    for (guild_id, name, rejections) in &candidate.registrations {
+                        if *rejections > 0 {
+                            storage
+                                .clear_prefetch_rejections(*guild_id, name, &leaderboard_id)
+                                .map_err(|e| e.to_string())?;
+                        }
+                    }
+                    count += 1;
+                    break;
+                }
+                Err(err) if err.is_auth() => {
+                    log::warn!(
+                        "Credentials were rejected prefetching leaderboard {leaderboard_id}: {err}"
+                    );
+                    for (guild_id, name, _) in &candidate.registrations {
+                        storage
+                            .add_prefetch_rejection(*guild_id, name, &leaderboard_id)
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Failed to prefetch leaderboard {leaderboard_id}: {err}");
+                    break;
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// How long to wait before prefetching again, which is longer outside December
+fn prefetch_interval(now: DateTime<Utc>) -> Duration {
+    let secs = if now.with_timezone(&est()).month() == 12 {
+        PREFETCH_INTERVAL_SECS
+    } else {
+        OFF_SEASON_PREFETCH_INTERVAL_SECS
+    };
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use serenity::model::prelude::GuildId;
+
+    use super::{prefetch, prefetch_interval, MAX_PREFETCH_REJECTIONS};
+    use crate::{
+        aoc::{AOCData, FixtureAocApi, LeaderboardAuth},
+        config::GuildConfig,
+        storage::{SqliteStorage, Storage},
+    };
+
+    const LEADERBOARD_JSON: &str = include_str!("../fixtures/leaderboard_2023.json");
+
+    fn register(storage: &SqliteStorage, guild_id: u64, auth: &str, leaderboard_id: &str) {
+        storage
+            .set_guild_config(
+                GuildId(guild_id),
+                &GuildConfig {
+                    name: "default".to_owned(),
+                    is_default: true,
+                    auth: LeaderboardAuth::ViewKey(auth.to_owned()),
+                    leaderboard_id: leaderboard_id.to_owned(),
+                },
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shared_leaderboards_are_fetched_once() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let register =
+            |guild_id, auth, leaderboard_id| register(&storage, guild_id, auth, leaderboard_id);
+        register(1, "one", "1");
+        register(2, "two", "1");
+        register(3, "three", "1,2");
+
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+            .with_leaderboard("2023", "2", LEADERBOARD_JSON);
+        let aoc_data = AOCData::with_api(api);
+
+        // Each board is fetched once, however many guilds use it
+        assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(2));
+        assert_eq!(aoc_data.api().request_count(), 2);
+
+        // And commands are then answered from the cache
+        aoc_data
+            .get_combined_leaderboard(
+                "2023",
+                &["1", "2"],
+                &LeaderboardAuth::ViewKey("three".to_owned()),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(aoc_data.api().request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials_fall_back_to_other_guilds() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        register(&storage, 1, "expired", "1");
+        register(&storage, 2, "valid", "1");
+        register(&storage, 3, "expired", "2");
+
+        let api = FixtureAocApi::default()
+            .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+            .with_leaderboard("2023", "2", LEADERBOARD_JSON)
+            .with_rejected_auth(LeaderboardAuth::ViewKey("expired".to_owned()));
+        let aoc_data = AOCData::with_api(api);
+
+        // The first board is fetched with the second guild's credentials after the first guild's
+        // are rejected, but the second board has no others to try
+        assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(1));
+        assert_eq!(aoc_data.api().request_count(), 3);
+
+        // The second board is tried again until its credentials have been rejected enough times,
+        // while the first is answered from the cache
+        for _ in 1..MAX_PREFETCH_REJECTIONS {
+            assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(1));
+        }
+        let requests = 3 + MAX_PREFETCH_REJECTIONS as usize - 1;
+        assert_eq!(aoc_data.api().request_count(), requests);
+        assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(1));
+        assert_eq!(aoc_data.api().request_count(), requests);
+
+        // Rejections are remembered after a restart, so the second board is still skipped and
+        // the first guild's credentials are tried after the second's
+        let aoc_data = AOCData::with_api(
+            FixtureAocApi::default()
+                .with_leaderboard("2023", "1", LEADERBOARD_JSON)
+                .with_leaderboard("2023", "2", LEADERBOARD_JSON)
+                .with_rejected_auth(LeaderboardAuth::ViewKey("expired".to_owned())),
+        );
+        assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(1));
+        assert_eq!(aoc_data.api().request_count(), 1);
+
+        // Until the guild registers them again, even if they're the same
+        register(&storage, 3, "expired", "2");
+        assert_eq!(prefetch(&aoc_data, &storage, 2023).await, Ok(1));
+        assert_eq!(aoc_data.api().request_count(), 2);
+    }
+
+    #[test]
+    fn test_slower_outside_december() {
+        let december = Utc.with_ymd_and_hms(2023, 12, 10, 12, 0, 0).unwrap();
+        let july = Utc.with_ymd_and_hms(2024, 7, 10, 12, 0, 0).unwrap();
+        assert_eq!(
+            prefetch_interval(december),
+            Duration::from_secs(15 * 60 + 30)
+        );
+        assert!(prefetch_interval(july) > prefetch_interval(december));
+    }
+}
@@ -0,0 +1,360 @@
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    aoc::{Leaderboard, LeaderboardMember},
+    calendar::EventCalendar,
+    config::LeaderboardOrdering,
+    format::format_duration,
+    scoring::compute_local_scores,
+};
+
+/// How long a member took to solve each part of a day, measured from when the puzzle unlocked
+pub struct DaySolveTimes<'a> {
+    pub member: &'a LeaderboardMember,
+    pub part1: Option<Duration>,
+    pub part2: Option<Duration>,
+}
+
+impl DaySolveTimes<'_> {
+    /// Time taken between solving part 1 and part 2
+    pub fn delta(&self) -> Option<Duration> {
+        Some(self.part2? - self.part1?)
+    }
+}
+
+/// The value a member is ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Points or stars, more is better
+    Points(usize),
+
+    /// A length of time, less is better. `None` if there is nothing to time yet
+    Time(Option<Duration>),
+
+    /// A number of days, more is better
+    Days(usize),
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Points(points) => write!(f, "{points}"),
+            Score::Time(Some(time)) => write!(f, "{}", format_duration(*time)),
+            Score::Time(None) => write!(f, "-"),
+            Score::Days(days) => write!(f, "{days}"),
+        }
+    }
+}
+
+/// A member of a leaderboard, along with the value they are ranked by
+pub struct RankedMember<'a> {
+    pub member: &'a LeaderboardMember,
+    pub score: Score,
+}
+
+/// Order the members of a leaderboard from first to last place
+pub fn rank_members(
+    leaderboard: &Leaderboard,
+    ordering: LeaderboardOrdering,
+) -> Vec<RankedMember<'_>> {
+    let year = leaderboard.event.parse().unwrap_or_default();
+
+    // Only recompute local scores if we need them
+    let computed_scores = match ordering {
+        LeaderboardOrdering::ComputedScore => compute_local_scores(leaderboard),
+        _ => HashMap::new(),
+    };
+
+    let mut members: Vec<_> = leaderboard
+        .members
+        .values()
+        .map(|member| RankedMember {
+            member,
+            score: match ordering {
+                LeaderboardOrdering::LocalScore => Score::Points(member.local_score),
+                LeaderboardOrdering::GlobalScore => Score::Points(member.global_score),
+                LeaderboardOrdering::Stars | LeaderboardOrdering::StarsThenLastStar => {
+                    Score::Points(member.stars)
+                }
+                LeaderboardOrdering::ComputedScore => {
+                    Score::Points(computed_scores.get(&member.id).copied().unwrap_or(0))
+                }
+                LeaderboardOrdering::MedianSolveTime => {
+                    let mut times: Vec<_> = completed_days(member, year)
+                        .map(|(_, part2)| part2)
+                        .collect();
+                    times.sort();
+                    Score::Time(median(&times))
+                }
+                LeaderboardOrdering::TotalSolveTime => Score::Time(
+                    completed_days(member, year)
+                        .map(|(_, part2)| part2)
+                        .reduce(|total, time| total + time),
+                ),
+                LeaderboardOrdering::AveragePartDelta => {
+                    let deltas: Vec<_> = completed_days(member, year)
+                        .map(|(part1, part2)| part2 - part1)
+                        .collect();
+                    Score::Time(mean(&deltas))
+                }
+                LeaderboardOrdering::CurrentStreak => {
+                    Score::Days(current_streak(member, year, Utc::now()))
+                }
+            },
+        })
+        .collect();
+
+    // The members map has no order of its own, so remaining ties are broken by id
+    members.sort_by(|a, b| compare_members(a, b, ordering).then(a.member.id.cmp(&b.member.id)));
+    members
+}
+
+fn compare_members(a: &RankedMember, b: &RankedMember, ordering: LeaderboardOrdering) -> Ordering {
+    let by_score = compare_scores(&a.score, &b.score);
+    let by_stars = b.member.stars.cmp(&a.member.stars);
+    let by_last_star = a.member.last_star_ts.cmp(&b.member.last_star_ts);
+    match ordering {
+        // Local score (default)
+        LeaderboardOrdering::LocalScore => by_score,
+        // Global score (ties broken by local score)
+        LeaderboardOrdering::GlobalScore => {
+            by_score.then(b.member.local_score.cmp(&a.member.local_score))
+        }
+        // Stars and computed score (ties broken by who got the most recent star first)
+        LeaderboardOrdering::Stars | LeaderboardOrdering::ComputedScore => {
+            by_score.then(by_last_star)
+        }
+        // AoC's tie-break rules, whoever got their last star first wins, then by local score
+        LeaderboardOrdering::StarsThenLastStar => by_score
+            .then(by_last_star)
+            .then(b.member.local_score.cmp(&a.member.local_score)),
+        // Time based orderings (ties broken by stars)
+        LeaderboardOrdering::MedianSolveTime | LeaderboardOrdering::AveragePartDelta => {
+            by_score.then(by_stars)
+        }
+        // Completing more days always beats a lower total, as it would include fewer days
+        LeaderboardOrdering::TotalSolveTime => completed_day_count(b.member)
+            .cmp(&completed_day_count(a.member))
+            .then(by_score),
+        // Streaks (ties broken by stars, then who got their last star first)
+        LeaderboardOrdering::CurrentStreak => by_score.then(by_stars).then(by_last_star),
+    }
+}
+
+/// Higher points and days are better, lower times are better but having no time is worst
+fn compare_scores(a: &Score, b: &Score) -> Ordering {
+    match (a, b) {
+        (Score::Points(a), Score::Points(b)) | (Score::Days(a), Score::Days(b)) => b.cmp(a),
+        (Score::Time(Some(a)), Score::Time(Some(b))) => a.cmp(b),
+        (Score::Time(Some(_)), Score::Time(None)) => Ordering::Less,
+        (Score::Time(None), Score::Time(Some(_))) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Part 1 and part 2 solve times for every day a member finished both parts of
+fn completed_days(
+    member: &LeaderboardMember,
+    year: i32,
+) -> impl Iterator<Item = (Duration, Duration)> + '_ {
+    member
+        .completion_day_level
+        .iter()
+        .filter_map(move |(day, parts)| {
+            let unlock = EventCalendar::new(year)
+                .unlock_time(*day as u32)
+                .unwrap_or_default();
+            let part1 = parts.get(&1)?.earned_at() - unlock;
+            let part2 = parts.get(&2)?.earned_at() - unlock;
+            Some((part1, part2))
+        })
+}
+
+/// How many days a member has finished both parts of
+fn completed_day_count(member: &LeaderboardMember) -> usize {
+    member
+        .completion_day_level
+        .values()
+        .filter(|parts| parts.len() == 2)
+        .count()
+}
+
+/// The average of some times
+fn mean(times: &[Duration]) -> Option<Duration> {
+    let total = times.iter().copied().reduce(|total, time| total + time)?;
+    Some(total / times.len() as i32)
+}
+
+/// The middle of some sorted times
+fn median(times: &[Duration]) -> Option<Duration> {
+    let middle = times.len() / 2;
+    match times.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((times[middle - 1] + times[middle]) / 2),
+        _ => Some(times[middle]),
+    }
+}
+
+/// How many days in a row a member has finished, counting back from the latest puzzle.
+/// The latest puzzle doesn't break the streak if it hasn't been finished yet.
+fn current_streak(member: &LeaderboardMember, year: i32, now: DateTime<Utc>) -> usize {
+    let is_complete = |day| {
+        member
+            .completion_day_level
+            .get(&day)
+            .is_some_and(|parts| parts.len() == 2)
+    };
+
+    let latest_day = EventCalendar::new(year).latest_unlocked_day(now) as usize;
+    let start_day = if is_complete(latest_day) {
+        latest_day
+    } else {
+        latest_day.saturating_sub(1)
+    };
+    (1..=start_day)
+        .rev()
+        .take_while(|day| is_complete(*day))
+        .count()
+}
+
+/// Members who solved part of a day, fastest first. Anyone who finished both parts is ranked by
+/// their part 2 time, ahead of those who only finished part 1.
+pub fn rank_day_solve_times(leaderboard: &Leaderboard, day: usize) -> Vec<DaySolveTimes<'_>> {
+    let Some(unlock) = leaderboard
+        .event
+        .parse()
+        .ok()
+        .and_then(|year| EventCalendar::new(year).unlock_time(day as u32))
+    else {
+        return Vec::new();
+    };
+
+    let mut times: Vec<_> = leaderboard
+        .members
+        .values()
+        .filter_map(|member| {
+            let parts = member.completion_day_level.get(&day)?;
+            let part_time = |part| parts.get(&part).map(|entry| entry.earned_at() - unlock);
+            Some(DaySolveTimes {
+                member,
+                part1: part_time(1),
+                part2: part_time(2),
+            })
+        })
+        .collect();
+
+    times.sort_by(|a, b| {
+        // Finishing both parts always beats finishing one (None sorts first, so flip it)
+        b.part2
+            .is_some()
+            .cmp(&a.part2.is_some())
+            .then(a.part2.cmp(&b.part2))
+            .then(a.part1.cmp(&b.part1))
+            .then(a.member.id.cmp(&b.member.id))
+    });
+    times
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::{current_streak, rank_day_solve_times, rank_members};
+    use crate::{aoc::Leaderboard, config::LeaderboardOrdering};
+
+    fn later_leaderboard() -> Leaderboard {
+        serde_json::from_str(include_str!("../fixtures/leaderboard_2023_later.json")).unwrap()
+    }
+
+    fn ranking(leaderboard: &Leaderboard, ordering: LeaderboardOrdering) -> Vec<(usize, String)> {
+        rank_members(leaderboard, ordering)
+            .iter()
+            .map(|ranked| (ranked.member.id, ranked.score.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_timing_orderings() {
+        let leaderboard = later_leaderboard();
+        let owned = |ranking: &[(usize, &str)]| -> Vec<(usize, String)> {
+            ranking
+                .iter()
+                .map(|(id, score)| (*id, score.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            ranking(&leaderboard, LeaderboardOrdering::MedianSolveTime),
+            owned(&[
+                (101, "40m00s"),
+                (102, "3h38m20s"),
+                (103, "3h40m00s"),
+                (105, "-")
+            ])
+        );
+        assert_eq!(
+            ranking(&leaderboard, LeaderboardOrdering::AveragePartDelta),
+            owned(&[
+                (101, "35m00s"),
+                (103, "2h40m00s"),
+                (102, "7h41m15s"),
+                (105, "-")
+            ])
+        );
+
+        // Bob has the longest total, but he's finished the most days
+        assert_eq!(
+            ranking(&leaderboard, LeaderboardOrdering::TotalSolveTime)
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            vec![102, 101, 103, 105]
+        );
+    }
+
+    #[test]
+    fn test_current_streak() {
+        let leaderboard = later_leaderboard();
+        let member = |id: &str| &leaderboard.members[id];
+
+        // Day 4 is still in progress, so it doesn't break Alice's streak yet
+        let day_4 = Utc.with_ymd_and_hms(2023, 12, 4, 17, 0, 0).unwrap();
+        assert_eq!(current_streak(member("102"), 2023, day_4), 4);
+        assert_eq!(current_streak(member("101"), 2023, day_4), 3);
+        assert_eq!(current_streak(member("103"), 2023, day_4), 0);
+
+        // But by day 5 it has
+        let day_5 = Utc.with_ymd_and_hms(2023, 12, 5, 17, 0, 0).unwrap();
+        assert_eq!(current_streak(member("101"), 2023, day_5), 0);
+    }
+
+    #[test]
+    fn test_rank_day_solve_times() {
+        let leaderboard: Leaderboard =
+            serde_json::from_str(include_str!("../fixtures/leaderboard_2023.json")).unwrap();
+
+        let ranked: Vec<_> = rank_day_solve_times(&leaderboard, 2)
+            .iter()
+            .map(|times| {
+                (
+                    times.member.id,
+                    times.part1.map(|t| t.num_minutes()),
+                    times.part2.map(|t| t.num_minutes()),
+                    times.delta().map(|t| t.num_minutes()),
+                )
+            })
+            .collect();
+
+        // Bob was faster on part 1, but only Alice finished part 2
+        assert_eq!(
+            ranked,
+            vec![
+                (101, Some(15), Some(40), Some(25)),
+                (102, Some(5), None, None)
+            ]
+        );
+        assert!(rank_day_solve_times(&leaderboard, 10).is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::Utc;
+use serenity::{
+    model::prelude::{GuildId, RoleId, UserId},
+    prelude::Context,
+};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::{
+    aoc::{AOCData, Leaderboard, LeaderboardCacheEntry},
+    calendar::EventCalendar,
+    config::RewardRequirement,
+    links::fetch_members,
+    ranking::rank_members,
+    storage::{SharedStorage, Storage},
+};
+
+/// Keep reward roles in sync with every leaderboard fetched from AoC
+pub async fn role_rewards(
+    aoc_data: Arc<AOCData>,
+    storage: SharedStorage,
+    ctx: Context,
+    mut refreshes: Receiver<Arc<LeaderboardCacheEntry>>,
+) {
+    loop {
+        let entry = match refreshes.recv().await {
+            Ok(entry) => entry,
+            Err(RecvError::Lagged(skipped)) => {
+                log::warn!("Skipped {skipped} leaderboard refreshes when syncing reward roles");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        // Rewards are only for the current event
+        if entry.leaderboard.event != EventCalendar::current(Utc::now()).year().to_string() {
+            continue;
+        }
+
+        // Find every guild whose default leaderboard includes this one that has rewards set up
+        let (role_reward_configs, guild_configs) =
+            match (storage.role_reward_configs(), storage.all_guild_configs()) {
+                (Ok(role_reward_configs), Ok(guild_configs)) => {
+                    (role_reward_configs, guild_configs)
+                }
+                (Err(error), _) | (_, Err(error)) => {
+                    log::error!("Failed to get config to sync reward roles: {error}");
+                    continue;
+                }
+            };
+        let guilds: Vec<_> = guild_configs
+            .into_iter()
+            .filter(|(_, guild_config)| {
+                guild_config.is_default
+                    && guild_config
+                        .leaderboard_ids()
+                        .contains(&entry.leaderboard_id.as_str())
+            })
+            .filter_map(|(guild_id, guild_config)| {
+                let rewards: Vec<_> = role_reward_configs
+                    .iter()
+                    .filter(|(_, reward)| reward.guild_id == guild_id)
+                    .map(|(role_id, reward)| (*role_id, reward.requirement))
+                    .collect();
+                (!rewards.is_empty()).then_some((guild_id, guild_config, rewards))
+            })
+            .collect();
+
+        for (guild_id, guild_config, rewards) in guilds {
+            // Rewards are for the guild's whole leaderboard, which may combine several boards.
+            // The others are usually cached, so this rarely needs another request.
+            let leaderboard = match aoc_data
+                .get_combined_leaderboard(
+                    &entry.leaderboard.event,
+                    &guild_config.leaderboard_ids(),
+                    &guild_config.auth,
+                    false,
+                )
+                .await
+            {
+                Ok(leaderboard) => leaderboard,
+                Err(error) => {
+                    log::warn!(
+                        "Failed to get leaderboard to sync reward roles in {guild_id}: {error}"
+                    );
+                    continue;
+                }
+            };
+
+            let links = match storage.member_links(guild_id) {
+                Ok(links) => links,
+                Err(error) => {
+                    log::error!(
+                        "Failed to get member links to sync reward roles in {guild_id}: {error}"
+                    );
+                    continue;
+                }
+            };
+            sync_guild_roles(&ctx, guild_id, &leaderboard.leaderboard, &rewards, &links).await;
+        }
+    }
+}
+
+async fn sync_guild_roles(
+    ctx: &Context,
+    guild_id: GuildId,
+    leaderboard: &Leaderboard,
+    rewards: &[(RoleId, RewardRequirement)],
+    links: &HashMap<usize, UserId>,
+) {
+    let qualified: Vec<_> = rewards
+        .iter()
+        .map(|(role_id, requirement)| (*role_id, qualifying_members(leaderboard, *requirement)))
+        .collect();
+
+    // Get every linked user at once, rather than one request each
+    let user_ids = links.values().copied().collect();
+    let mut members = match fetch_members(&ctx.http, guild_id, &user_ids).await {
+        Ok(members) => members,
+        Err(error) => {
+            log::warn!("Failed to get linked users in {guild_id}: {error}");
+            return;
+        }
+    };
+
+    for (member_id, user_id) in links {
+        let Some(member) = members.get_mut(user_id) else {
+            continue;
+        };
+
+        for (role_id, members) in &qualified {
+            let has_role = member.roles.contains(role_id);
+            let result = match (members.contains(member_id), has_role) {
+                (true, false) => member.add_role(&ctx.http, *role_id).await,
+                (false, true) => member.remove_role(&ctx.http, *role_id).await,
+                _ => continue,
+            };
+            match result {
+                Ok(()) => log::info!(
+                    "{} reward role {role_id} for {user_id} in {guild_id}",
+                    if has_role { "Removed" } else { "Added" }
+                ),
+                Err(error) => log::warn!(
+                    "Failed to update reward role {role_id} for {user_id} in {guild_id}: {error}"
+                ),
+            }
+        }
+    }
+}
+
+/// Take every reward role in a guild away from a user, once they're no longer linked to the
+/// member that earned them
+pub async fn remove_reward_roles(
+    ctx: &Context,
+    storage: &dyn Storage,
+    guild_id: GuildId,
+    user_id: UserId,
+) {
+    let role_ids: Vec<RoleId> = match storage.role_reward_configs() {
+        Ok(configs) => configs
+            .into_iter()
+            .filter(|(_, reward)| reward.guild_id == guild_id)
+            .map(|(role_id, _)| role_id)
+            .collect(),
+        Err(error) => {
+            log::error!("Failed to get reward roles to remove in {guild_id}: {error}");
+            return;
+        }
+    };
+    if role_ids.is_empty() {
+        return;
+    }
+
+    let mut member = match guild_id.member(&ctx.http, user_id).await {
+        Ok(member) => member,
+        Err(error) => {
+            log::warn!("Failed to get unlinked user {user_id} in {guild_id}: {error}");
+            return;
+        }
+    };
+    for role_id in role_ids {
+        if !member.roles.contains(&role_id) {
+            continue;
+        }
+        match member.remove_role(&ctx.http, role_id).await {
+            Ok(()) => log::info!("Removed reward role {role_id} from unlinked {user_id} in {guild_id}"),
+            Err(error) => log::warn!(
+                "Failed to remove reward role {role_id} from unlinked {user_id} in {guild_id}: {error}"
+            ),
+        }
+    }
+}
+
+/// The IDs of every member of a leaderboard that meets a requirement
+pub fn qualifying_members(
+    leaderboard: &Leaderboard,
+    requirement: RewardRequirement,
+) -> HashSet<usize> {
+    match requirement {
+        RewardRequirement::Stars(stars) => leaderboard
+            .members
+            .values()
+            .filter(|member| member.stars >= stars)
+            .map(|member| member.id)
+            .collect(),
+        RewardRequirement::AllStars => {
+            let year = leaderboard.event.parse().unwrap_or_default();
+            qualifying_members(
+                leaderboard,
+                RewardRequirement::Stars(2 * EventCalendar::new(year).puzzle_count() as usize),
+            )
+        }
+        // Members without any stars haven't placed, even on small leaderboards
+        RewardRequirement::TopRank { rank, ordering } => rank_members(leaderboard, ordering)
+            .into_iter()
+            .filter(|ranked| ranked.member.stars > 0)
+            .take(rank)
+            .map(|ranked| ranked.member.id)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::qualifying_members;
+    use crate::{
+        aoc::Leaderboard,
+        config::{LeaderboardOrdering, RewardRequirement},
+    };
+
+    fn leaderboard() -> Leaderboard {
+        serde_json::from_str(include_str!("../fixtures/leaderboard_2023.json")).unwrap()
+    }
+
+    #[test]
+    fn test_star_requirements() {
+        let leaderboard = leaderboard();
+        assert_eq!(
+            qualifying_members(&leaderboard, RewardRequirement::Stars(3)),
+            HashSet::from([101, 102])
+        );
+        assert!(qualifying_members(&leaderboard, RewardRequirement::AllStars).is_empty());
+    }
+
+    #[test]
+    fn test_top_rank_requirement() {
+        let leaderboard = leaderboard();
+        let requirement = |rank| RewardRequirement::TopRank {
+            rank,
+            ordering: LeaderboardOrdering::LocalScore,
+        };
+        assert_eq!(
+            qualifying_members(&leaderboard, requirement(2)),
+            HashSet::from([101, 102])
+        );
+
+        // Carol has no stars, so doesn't make the top 4
+        assert_eq!(
+            qualifying_members(&leaderboard, requirement(4)),
+            HashSet::from([101, 102, 103])
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// How far ahead to look for the next time a schedule is due.
+/// The 29th of February can be up to 8 years apart (e.g. 2096 and 2104).
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+const MONTH_NAMES: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// The shortest time a schedule can leave between two posts, in minutes
+pub const MIN_POST_INTERVAL_MINS: u32 = 30;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// When a daily should be posted, written like a cron expression: `minute hour day month weekday`.
+///
+/// Each field is `*`, a value, a range like `1-12`, or a list of them like `9,17`, and can step
+/// through a range with `/` like `*/15`. Months and weekdays can also be named (`DEC`, `MON-FRI`).
+/// Unlike cron, a time has to match both the day and the weekday fields.
+///
+/// Posts have to be at least [`MIN_POST_INTERVAL_MINS`] apart, counting every hour and minute
+/// combination the schedule allows and the wrap around from one day to the next.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+}
+
+impl Schedule {
+    /// Every day at the start of an hour
+    pub fn at_hour(hour: usize) -> Self {
+        format!("0 {} * * *", hour.min(23))
+            .parse()
+            .expect("every hour is a valid schedule")
+    }
+
+    /// Whether the schedule is due at a time, to the minute
+    pub fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        self.matches_date(time.date_naive())
+            && has_bit(self.hours, time.hour())
+            && has_bit(self.minutes, time.minute())
+    }
+
+    /// The first time after `time` that the schedule is due, if it ever is
+    pub fn next_after<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = time.timezone();
+        let start = time.date_naive();
+        for offset in 0..MAX_SEARCH_DAYS {
+            let date = start + Duration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in bits(self.hours) {
+                for minute in bits(self.minutes) {
+                    // Times skipped by daylight saving changes never happen
+                    let Some(candidate) = date
+                        .and_hms_opt(hour, minute, 0)
+                        .and_then(|local| timezone.from_local_datetime(&local).earliest())
+                    else {
+                        continue;
+                    };
+                    if candidate > *time {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        has_bit(self.days, date.day())
+            && has_bit(self.months, date.month())
+            && has_bit(self.weekdays, date.weekday().num_days_from_sunday())
+    }
+}
+
+/// The closest two consecutive times of day that hours and minutes allow, as minutes since midnight
+/// and the gap between them. The last time of a day is followed by the first time of the next.
+fn shortest_gap(hours: u64, minutes: u64) -> Option<(u32, u32, u32)> {
+    let times: Vec<_> = bits(hours)
+        .flat_map(|hour| bits(minutes).map(move |minute| hour * 60 + minute))
+        .collect();
+    let (first, last) = (*times.first()?, *times.last()?);
+    times
+        .windows(2)
+        .map(|pair| (pair[0], pair[1], pair[1] - pair[0]))
+        .chain([(last, first, first + MINUTES_PER_DAY - last)])
+        .min_by_key(|(_, _, gap)| *gap)
+}
+
+fn has_bit(mask: u64, bit: u32) -> bool {
+    mask & (1 << bit) != 0
+}
+
+fn bits(mask: u64) -> impl Iterator<Item = u32> {
+    (0..64).filter(move |bit| has_bit(mask, *bit))
+}
+
+/// Parse one field of a schedule into a bit mask of the values it allows
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let value = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+        {
+            Some(index) => index as u32 + min,
+            None => text
+                .parse()
+                .map_err(|_| format!("`{text}` isn't a number"))?,
+        };
+        if !(min..=max).contains(&value) {
+            return Err(format!("`{value}` must be between {min} and {max}"));
+        }
+        Ok(value)
+    };
+
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("`{step}` isn't a valid step"))?,
+            ),
+            None => (item, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // A single value with a step, like `5/15`, steps until the end of the field
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start > end {
+            return Err(format!("`{range}` is a backwards range"));
+        }
+        for value in (start..=end).step_by(step) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            ));
+        };
+
+        // Sunday can be written as 0 or 7
+        let mut weekdays = parse_field(weekdays, 0, 7, WEEKDAY_NAMES)?;
+        if has_bit(weekdays, 7) {
+            weekdays |= 1;
+        }
+
+        // Posting too often would just be noise
+        let minutes = parse_field(minutes, 0, 59, &[])?;
+        let hours = parse_field(hours, 0, 23, &[])?;
+        if let Some((from, to, gap)) = shortest_gap(hours, minutes) {
+            if gap < MIN_POST_INTERVAL_MINS {
+                return Err(format!(
+                    "posts have to be at least {MIN_POST_INTERVAL_MINS} minutes apart, \
+                     but this posts at {:02}:{:02} and again {gap} minutes later at {:02}:{:02}",
+                    from / 60,
+                    from % 60,
+                    to / 60,
+                    to % 60
+                ));
+            }
+        }
+
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes,
+            hours,
+            days: parse_field(days, 1, 31, &[])?,
+            months: parse_field(months, 1, 12, MONTH_NAMES)?,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        expression.parse()
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.expression
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::Schedule;
+
+    fn est() -> FixedOffset {
+        FixedOffset::east_opt(-5 * 60 * 60).unwrap()
+    }
+
+    #[test]
+    fn test_weekday_schedule() {
+        let schedule: Schedule = "0 9,17 * 12 MON-FRI".parse().unwrap();
+
+        // Friday the 1st of December 2023
+        let friday = est().with_ymd_and_hms(2023, 12, 1, 9, 0, 0).unwrap();
+        assert!(schedule.matches(&friday));
+        assert_eq!(
+            schedule.next_after(&friday),
+            Some(est().with_ymd_and_hms(2023, 12, 1, 17, 0, 0).unwrap())
+        );
+
+        // Skips the weekend
+        let evening = est().with_ymd_and_hms(2023, 12, 1, 17, 0, 0).unwrap();
+        assert_eq!(
+            schedule.next_after(&evening),
+            Some(est().with_ymd_and_hms(2023, 12, 4, 9, 0, 0).unwrap())
+        );
+
+        // Waits until next December
+        let new_year = est().with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            schedule.next_after(&new_year),
+            Some(est().with_ymd_and_hms(2024, 12, 2, 9, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_ranges_and_steps() {
+        let schedule: Schedule = "30 */6 1-12 dec 0,7".parse().unwrap();
+        let time = est().with_ymd_and_hms(2023, 12, 3, 12, 30, 0).unwrap();
+        assert!(schedule.matches(&time));
+        assert_eq!(
+            schedule.next_after(&time),
+            Some(est().with_ymd_and_hms(2023, 12, 3, 18, 30, 0).unwrap())
+        );
+        assert_eq!(
+            schedule.next_after(&est().with_ymd_and_hms(2023, 12, 3, 18, 30, 0).unwrap()),
+            Some(est().with_ymd_and_hms(2023, 12, 10, 0, 30, 0).unwrap())
+        );
+
+        // A day that doesn't exist is never due
+        let never: Schedule = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(never.next_after(&time), None);
+    }
+
+    #[test]
+    fn test_at_hour() {
+        let schedule = Schedule::at_hour(9);
+        assert_eq!(schedule, "0 9 * * *".parse().unwrap());
+        assert_eq!(schedule.to_string(), "0 9 * * *");
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        for expression in [
+            "0 9 * *",
+            "60 9 * * *",
+            "0 9 0 * *",
+            "0 9 * 13 *",
+            "0 17-9 * * *",
+            "*/0 * * * *",
+            "0 9 * * FUNDAY",
+        ] {
+            assert!(expression.parse::<Schedule>().is_err(), "{expression}");
+        }
+    }
+
+    #[test]
+    fn test_minimum_post_interval() {
+        // Several minutes are fine as long as posts are far enough apart
+        for expression in [
+            "15 * * 12 *",
+            "0,30 9,17 * 12 *",
+            "0,30 */2 * * *",
+            "0,45 9 * * *",
+        ] {
+            assert!(expression.parse::<Schedule>().is_ok(), "{expression}");
+        }
+
+        // But anything closer isn't
+        for expression in ["* 9 * * *", "*/15 * * 12 *", "0,20 9 * * *", "0-1 * * * *"] {
+            let error = expression.parse::<Schedule>().unwrap_err();
+            assert!(
+                error.contains("at least 30 minutes"),
+                "{expression}: {error}"
+            );
+        }
+
+        // Including from the end of one hour or day to the start of the next
+        let error = "0,40 0,23 * * *".parse::<Schedule>().unwrap_err();
+        assert!(
+            error.contains("23:40 and again 20 minutes later at 00:00"),
+            "{error}"
+        );
+        let error = "10,50 * * * *".parse::<Schedule>().unwrap_err();
+        assert!(error.contains("20 minutes later"), "{error}");
+    }
+}
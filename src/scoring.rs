@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use crate::aoc::{Leaderboard, LeaderboardMember};
+
+/// Days that AoC awarded no points for, because of problems with the puzzle or the servers
+const ZEROED_DAYS: &[(i32, usize)] = &[(2018, 6), (2020, 1)];
+
+/// When a member earned a star, as (get_star_ts, star_index, member id) so they sort in order
+type EarnedStar = (usize, usize, usize);
+
+/// A member whose local score from AoC doesn't match the one we computed
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScoreMismatch {
+    pub member_id: usize,
+    pub reported: usize,
+    pub computed: usize,
+}
+
+/// Whether a day of an event gave out points
+pub fn is_scored_day(year: i32, day: usize) -> bool {
+    !ZEROED_DAYS.contains(&(year, day))
+}
+
+/// Recompute the local score of every member from when they earned their stars.
+///
+/// For every star, the first member to earn it gets a point for each member of the leaderboard,
+/// the second gets one fewer, and so on.
+pub fn compute_local_scores(leaderboard: &Leaderboard) -> HashMap<usize, usize> {
+    let year = leaderboard.event.parse().unwrap_or_default();
+    let member_count = leaderboard.members.len();
+
+    // Collect when each star was earned, keyed by (day, part)
+    let mut stars: HashMap<(usize, usize), Vec<EarnedStar>> = HashMap::new();
+    for member in leaderboard.members.values() {
+        for (day, parts) in &member.completion_day_level {
+            for (part, entry) in parts {
+                stars.entry((*day, *part)).or_default().push((
+                    entry.get_star_ts,
+                    entry.star_index,
+                    member.id,
+                ));
+            }
+        }
+    }
+
+    // Award points in the order the stars were earned
+    let mut scores: HashMap<usize, usize> = leaderboard
+        .members
+        .values()
+        .map(|member| (member.id, 0))
+        .collect();
+    for ((day, _), mut earned) in stars {
+        if !is_scored_day(year, day) {
+            continue;
+        }
+
+        earned.sort();
+        for (position, (_, _, member_id)) in earned.into_iter().enumerate() {
+            *scores.entry(member_id).or_default() += member_count.saturating_sub(position);
+        }
+    }
+
+    scores
+}
+
+/// Merge several leaderboards into one, as if every member was on a single leaderboard.
+/// Members of more than one leaderboard are only counted once, and local scores are recomputed
+/// over everyone.
+pub fn merge_leaderboards(leaderboards: &[&Leaderboard]) -> Leaderboard {
+    let mut members: HashMap<String, LeaderboardMember> = HashMap::new();
+    for member in leaderboards
+        .iter()
+        .flat_map(|leaderboard| leaderboard.members.values())
+    {
+        // Leaderboards can be fetched at different times, so keep each member's latest progress
+        let key = member.id.to_string();
+        let is_newer = members.get(&key).is_none_or(|existing| {
+            (member.stars, member.last_star_ts) > (existing.stars, existing.last_star_ts)
+        });
+        if is_newer {
+            members.insert(key, member.clone());
+        }
+    }
+
+    let mut merged = Leaderboard {
+        event: leaderboards
+            .first()
+            .map(|leaderboard| leaderboard.event.clone())
+            .unwrap_or_default(),
+        members,
+    };
+    for (member_id, score) in compute_local_scores(&merged) {
+        if let Some(member) = merged.members.get_mut(&member_id.to_string()) {
+            member.local_score = score;
+        }
+    }
+    merged
+}
+
+/// Find every member whose local score from AoC differs from the computed one
+pub fn verify_local_scores(leaderboard: &Leaderboard) -> Vec<ScoreMismatch> {
+    let computed_scores = compute_local_scores(leaderboard);
+    let mut mismatches: Vec<_> = leaderboard
+        .members
+        .values()
+        .filter_map(|member| {
+            let computed = computed_scores.get(&member.id).copied().unwrap_or(0);
+            (computed != member.local_score).then_some(ScoreMismatch {
+                member_id: member.id,
+                reported: member.local_score,
+                computed,
+            })
+        })
+        .collect();
+    mismatches.sort_by_key(|mismatch| mismatch.member_id);
+    mismatches
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compute_local_scores, merge_leaderboards, verify_local_scores, ScoreMismatch};
+    use crate::aoc::Leaderboard;
+
+    fn fixture(json: &str) -> Leaderboard {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_computed_scores_match_api() {
+        for json in [
+            include_str!("../fixtures/leaderboard_2023.json"),
+            include_str!("../fixtures/leaderboard_2023_later.json"),
+        ] {
+            let leaderboard = fixture(json);
+            assert_eq!(verify_local_scores(&leaderboard), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_merge_leaderboards() {
+        // Split the fixture across two leaderboards that share a member
+        let full = fixture(include_str!("../fixtures/leaderboard_2023.json"));
+        let mut first = full.clone();
+        let mut second = full.clone();
+        first.members.retain(|_, member| member.id <= 102);
+        second.members.retain(|_, member| member.id >= 102);
+
+        // Merging them gives back the scores of the full leaderboard
+        let merged = merge_leaderboards(&[&first, &second]);
+        assert_eq!(merged.members.len(), full.members.len());
+        assert_eq!(verify_local_scores(&merged), vec![]);
+        for (key, member) in &full.members {
+            assert_eq!(merged.members[key].local_score, member.local_score);
+        }
+    }
+
+    #[test]
+    fn test_zeroed_days() {
+        // Pretend the fixture is from 2020, where day 1 gave no points
+        let mut leaderboard = fixture(include_str!("../fixtures/leaderboard_2023.json"));
+        leaderboard.event = "2020".to_owned();
+
+        let scores = compute_local_scores(&leaderboard);
+        assert_eq!(scores[&101], 15);
+        assert_eq!(scores[&102], 4);
+        assert_eq!(scores[&103], 0);
+        assert_eq!(
+            verify_local_scores(&leaderboard)[0],
+            ScoreMismatch {
+                member_id: 101,
+                reported: 23,
+                computed: 15,
+            }
+        );
+    }
+}
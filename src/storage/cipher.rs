@@ -0,0 +1,106 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+
+use super::{StorageError, StorageResult};
+
+/// Marks a stored value as encrypted, so values saved before encryption can still be read
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+const NONCE_LENGTH: usize = 24;
+
+/// Encrypts leaderboard credentials before they're stored
+pub struct TokenCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl TokenCipher {
+    /// Create a cipher from a base64 encoded 32 byte key
+    pub fn from_key(key: &str) -> Result<Self, String> {
+        let key = STANDARD
+            .decode(key.trim())
+            .map_err(|_| "key must be base64 encoded".to_owned())?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|_| format!("key must be 32 bytes, not {}", key.len()))?;
+        Ok(Self { cipher })
+    }
+
+    /// A new random base64 encoded key
+    pub fn generate_key() -> String {
+        STANDARD.encode(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    pub fn encrypt(&self, token: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, token.as_bytes())
+            .expect("encrypting in memory can't fail");
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(payload))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> StorageResult<String> {
+        // Values from before encryption was added are stored as is
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_owned());
+        };
+
+        let payload = STANDARD
+            .decode(encoded)
+            .map_err(|_| StorageError::Corrupt("encrypted token isn't base64".to_owned()))?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(StorageError::Corrupt(
+                "encrypted token is too short".to_owned(),
+            ));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let token = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| StorageError::WrongKey)?;
+        String::from_utf8(token)
+            .map_err(|_| StorageError::Corrupt("decrypted token isn't UTF-8".to_owned()))
+    }
+
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(ENCRYPTED_PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TokenCipher;
+    use crate::storage::StorageError;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let cipher = TokenCipher::from_key(&TokenCipher::generate_key()).unwrap();
+        let encrypted = cipher.encrypt("session");
+        assert!(TokenCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.contains("session"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "session");
+
+        // The same token encrypts differently each time
+        assert_ne!(cipher.encrypt("session"), encrypted);
+
+        // Tokens saved before encryption are read as is
+        assert_eq!(cipher.decrypt("plain").unwrap(), "plain");
+
+        // A different key can't read them
+        let other = TokenCipher::from_key(&TokenCipher::generate_key()).unwrap();
+        assert!(matches!(
+            other.decrypt(&encrypted),
+            Err(StorageError::WrongKey)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(TokenCipher::from_key("not base64!").is_err());
+        assert!(TokenCipher::from_key("c2hvcnQ=").is_err());
+    }
+}
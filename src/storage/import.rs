@@ -0,0 +1,123 @@
+use std::{fs, io, path::Path};
+
+use crate::config::{Config, CONFIG_FILE};
+
+use super::{SqliteStorage, Storage, StorageError};
+
+/// Copy everything from an old `config.json` into an empty database.
+/// The file is renamed afterwards so it's only ever imported once.
+pub fn import_config_file(storage: &SqliteStorage) -> Result<bool, ImportError> {
+    let Some(config) = Config::load(CONFIG_FILE)? else {
+        return Ok(false);
+    };
+
+    // Import everything or nothing, so a failed import is tried again next time. Checking for
+    // existing data in the same transaction means nothing set up since can be clobbered.
+    let imported = storage.in_transaction(|storage| {
+        if !storage.is_empty()? {
+            log::warn!("Not importing {CONFIG_FILE} because the database already has data in it");
+            return Ok(false);
+        }
+        import_config(storage, &config)?;
+        Ok(true)
+    })?;
+    if !imported {
+        return Ok(false);
+    }
+
+    // Only once it's saved, make sure it isn't imported again
+    let imported_path = format!("{CONFIG_FILE}.imported");
+    fs::rename(CONFIG_FILE, Path::new(&imported_path))?;
+    Ok(true)
+}
+
+fn import_config(storage: &dyn Storage, config: &Config) -> Result<(), StorageError> {
+    for (guild_id, guild_config) in &config.guild_configs {
+        storage.set_guild_config(*guild_id, guild_config)?;
+    }
+    for (channel_id, lb_config) in &config.daily_leaderboard_configs {
+        storage.set_daily_leaderboard_config(*channel_id, lb_config)?;
+    }
+    for (channel_id, pz_config) in &config.daily_puzzle_configs {
+        storage.set_daily_puzzle_config(*channel_id, pz_config)?;
+    }
+    for (channel_id, notify_config) in &config.star_notify_configs {
+        storage.set_star_notify_config(*channel_id, notify_config)?;
+    }
+    for (role_id, reward_config) in &config.role_reward_configs {
+        storage.set_role_reward_config(*role_id, reward_config)?;
+    }
+    for (guild_id, links) in &config.member_links {
+        for (member_id, user_id) in links {
+            storage.link_member(*guild_id, *member_id, *user_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "couldn't read {CONFIG_FILE}: {err}"),
+            ImportError::Storage(err) => write!(f, "couldn't save imported config: {err}"),
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+impl From<StorageError> for ImportError {
+    fn from(err: StorageError) -> Self {
+        ImportError::Storage(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+    use super::import_config;
+    use crate::{
+        aoc::LeaderboardAuth,
+        config::Config,
+        storage::{SqliteStorage, Storage},
+    };
+
+    #[test]
+    fn test_import_config() {
+        // A config.json from before notifications, links and rewards existed
+        let config: Config = serde_json::from_str(
+            r#"{
+                "guild_configs": {"1": {"session_token": "abc", "leaderboard_id": "1234"}},
+                "daily_leaderboard_configs": {"10": {"guild_id": "1", "hour": 9, "ordering": "Stars"}},
+                "daily_puzzle_configs": {"11": {"guild_id": "1", "hour": 0}}
+            }"#,
+        )
+        .unwrap();
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        import_config(&storage, &config).unwrap();
+
+        let guild_config = storage.guild_config(GuildId(1), None).unwrap().unwrap();
+        assert!(matches!(guild_config.auth, LeaderboardAuth::SessionToken(ref t) if t == "abc"));
+        assert_eq!(
+            storage.daily_leaderboard_configs().unwrap()[&ChannelId(10)].hour,
+            9
+        );
+        assert!(storage
+            .daily_puzzle_configs()
+            .unwrap()
+            .contains_key(&ChannelId(11)));
+        assert!(storage.member_links(GuildId(1)).unwrap().is_empty());
+        assert!(!storage.unlink_user(GuildId(1), UserId(1)).unwrap());
+    }
+}
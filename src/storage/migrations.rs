@@ -0,0 +1,273 @@
+use std::path::Path;
+
+use chrono::Utc;
+use rusqlite::{Connection, Transaction};
+
+use super::{StorageError, StorageResult};
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Every change to the database schema, in order. The schema version stored in the database
+/// (`PRAGMA user_version`) is the number of these that have been applied.
+/// Never edit or reorder a migration once released, add a new one instead.
+const MIGRATIONS: &[Migration] = &[
+    create_tables,
+    named_leaderboards,
+    daily_schedules,
+    daily_last_posted,
+    guild_settings,
+    daily_failures,
+    notify_boards,
+    prefetch_rejections,
+];
+
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Bring a database up to the latest schema version, backing it up first if it has data in it
+pub fn migrate(connection: &mut Connection, path: Option<&Path>) -> StorageResult<()> {
+    let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > LATEST_VERSION {
+        return Err(StorageError::UnsupportedVersion(version));
+    }
+    if version == LATEST_VERSION {
+        return Ok(());
+    }
+
+    // Keep a copy of the database as it was, in case a migration goes wrong
+    let has_tables: bool = connection.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    )?;
+    if let Some(path) = path.filter(|_| has_tables) {
+        let backup_path = format!(
+            "{}.v{version}-{}.bak",
+            path.display(),
+            Utc::now().timestamp()
+        );
+        connection.execute("VACUUM INTO ?", [&backup_path])?;
+        log::info!("Backed up database to {backup_path} before migrating");
+    }
+
+    // Apply each migration in its own transaction so a failure leaves a consistent version
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let next_version = index as u32 + 1;
+        let transaction = connection.transaction()?;
+        migration(&transaction)?;
+        transaction.pragma_update(None, "user_version", next_version)?;
+        transaction.commit()?;
+        log::info!("Migrated database to schema version {next_version}");
+    }
+    Ok(())
+}
+
+/// Version 1, the tables imported from `config.json`.
+/// Databases created before versioning already have these, so they're only created if missing.
+fn create_tables(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS guild_configs (
+            guild_id INTEGER PRIMARY KEY,
+            leaderboard_id TEXT NOT NULL,
+            auth_kind TEXT NOT NULL,
+            auth_value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS daily_leaderboard_configs (
+            channel_id INTEGER PRIMARY KEY,
+            guild_id INTEGER NOT NULL,
+            hour INTEGER NOT NULL,
+            ordering TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS daily_puzzle_configs (
+            channel_id INTEGER PRIMARY KEY,
+            guild_id INTEGER NOT NULL,
+            hour INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS star_notify_configs (
+            channel_id INTEGER PRIMARY KEY,
+            guild_id INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS role_reward_configs (
+            role_id INTEGER PRIMARY KEY,
+            guild_id INTEGER NOT NULL,
+            requirement TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS member_links (
+            guild_id INTEGER NOT NULL,
+            member_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            PRIMARY KEY (guild_id, member_id),
+            UNIQUE (guild_id, user_id)
+        );
+        ",
+    )
+}
+
+/// Version 2, guilds can register several named leaderboards.
+/// Existing registrations become the default leaderboard of their guild.
+fn named_leaderboards(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE guild_leaderboards (
+            guild_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            is_default INTEGER NOT NULL,
+            leaderboard_id TEXT NOT NULL,
+            auth_kind TEXT NOT NULL,
+            auth_value TEXT NOT NULL,
+            PRIMARY KEY (guild_id, name)
+        );
+        INSERT INTO guild_leaderboards
+            SELECT guild_id, 'default', 1, leaderboard_id, auth_kind, auth_value FROM guild_configs;
+        DROP TABLE guild_configs;
+        ALTER TABLE daily_leaderboard_configs ADD COLUMN board TEXT;
+        ",
+    )
+}
+
+/// Version 3, dailies can be posted on a schedule instead of once a day
+fn daily_schedules(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE daily_leaderboard_configs ADD COLUMN schedule TEXT;
+        ALTER TABLE daily_puzzle_configs ADD COLUMN schedule TEXT;
+        ",
+    )
+}
+
+/// Version 4, dailies remember when they were last posted so missed posts can be caught up
+fn daily_last_posted(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE daily_leaderboard_configs ADD COLUMN last_posted INTEGER;
+        ALTER TABLE daily_puzzle_configs ADD COLUMN last_posted INTEGER;
+        ",
+    )
+}
+
+/// Version 5, guilds can pick the timezone their times are shown and scheduled in
+fn guild_settings(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE guild_settings (
+            guild_id INTEGER PRIMARY KEY,
+            timezone TEXT
+        );
+        ",
+    )
+}
+
+/// Version 6, dailies count how many posts in a row have failed so they can be turned off
+fn daily_failures(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE daily_leaderboard_configs ADD COLUMN failures INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE daily_puzzle_configs ADD COLUMN failures INTEGER NOT NULL DEFAULT 0;
+        ",
+    )
+}
+
+/// Version 7, star notifications can watch any of a guild's leaderboards
+fn notify_boards(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE star_notify_configs ADD COLUMN board TEXT;
+        ",
+    )
+}
+
+/// Version 8, prefetching remembers which credentials AoC keeps rejecting across restarts
+fn prefetch_rejections(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE prefetch_rejections (
+            guild_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            leaderboard_id TEXT NOT NULL,
+            rejections INTEGER NOT NULL,
+            PRIMARY KEY (guild_id, name, leaderboard_id)
+        );
+        ",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_dir, remove_dir_all},
+    };
+
+    use rusqlite::Connection;
+
+    use super::{migrate, LATEST_VERSION};
+    use crate::storage::StorageError;
+
+    fn version(connection: &Connection) -> u32 {
+        connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrate_new_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection, None).unwrap();
+        assert_eq!(version(&connection), LATEST_VERSION);
+
+        // Migrating again does nothing
+        migrate(&mut connection, None).unwrap();
+        assert_eq!(version(&connection), LATEST_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_database() {
+        let dir = temp_dir().join(format!("aoc-friend-migrations-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("aoc-friend.db");
+
+        // A database from before schema versions existed
+        let mut connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE guild_configs (
+                    guild_id INTEGER PRIMARY KEY,
+                    leaderboard_id TEXT NOT NULL,
+                    auth_kind TEXT NOT NULL,
+                    auth_value TEXT NOT NULL
+                );
+                INSERT INTO guild_configs VALUES (1, '1234', 'view_key', 'key');",
+            )
+            .unwrap();
+
+        migrate(&mut connection, Some(&path)).unwrap();
+        assert_eq!(version(&connection), LATEST_VERSION);
+        let (name, leaderboard_id): (String, String) = connection
+            .query_row(
+                "SELECT name, leaderboard_id FROM guild_leaderboards",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(name, "default");
+        assert_eq!(leaderboard_id, "1234");
+
+        // The old database was backed up next to it
+        assert_eq!(read_dir(&dir).unwrap().count(), 2);
+
+        drop(connection);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_newer_database_is_rejected() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", LATEST_VERSION + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&mut connection, None),
+            Err(StorageError::UnsupportedVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+mod cipher;
+mod import;
+mod migrations;
+mod sqlite;
+
+use std::{collections::HashMap, env, fmt, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serenity::model::prelude::{ChannelId, GuildId, RoleId, UserId};
+
+use crate::config::{
+    DailyLeaderboardConfig, DailyPuzzleConfig, GuildConfig, RoleRewardConfig, StarNotifyConfig,
+    DEFAULT_TIMEZONE,
+};
+
+pub use cipher::TokenCipher;
+pub use import::import_config_file;
+pub use sqlite::SqliteStorage;
+
+const DEFAULT_DATABASE_FILE: &str = "aoc-friend.db";
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The database couldn't be read or written
+    Database(rusqlite::Error),
+    /// A stored value couldn't be understood
+    Corrupt(String),
+    /// The database was created by a newer version of the bot
+    UnsupportedVersion(u32),
+    /// A stored token was encrypted with a different key
+    WrongKey,
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Database(err) => write!(f, "database error: {err}"),
+            StorageError::Corrupt(details) => write!(f, "corrupt stored value: {details}"),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "database schema version {version} is newer than this version of the bot supports"
+            ),
+            StorageError::WrongKey => write!(
+                f,
+                "stored tokens were encrypted with a different key than TOKEN_KEY"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Database(err)
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+/// Everything the bot remembers about the guilds it's in.
+/// Each method is applied on its own, so concurrent commands can't overwrite each other.
+pub trait Storage: Send + Sync {
+    /// The leaderboard registered to a guild with a name, or the guild's default leaderboard
+    fn guild_config(
+        &self,
+        guild_id: GuildId,
+        name: Option<&str>,
+    ) -> StorageResult<Option<GuildConfig>>;
+    /// Every leaderboard registered to a guild, default first
+    fn guild_configs(&self, guild_id: GuildId) -> StorageResult<Vec<GuildConfig>>;
+    /// Every leaderboard registered to any guild
+    fn all_guild_configs(&self) -> StorageResult<Vec<(GuildId, GuildConfig)>>;
+    /// Register a leaderboard, replacing any with the same name. A default leaderboard replaces
+    /// the guild's previous default, and a guild's first leaderboard is always its default.
+    fn set_guild_config(&self, guild_id: GuildId, config: &GuildConfig) -> StorageResult<()>;
+    /// Remove a leaderboard, picking a new default for the guild if it was the default
+    fn remove_guild_config(&self, guild_id: GuildId, name: &str) -> StorageResult<bool>;
+
+    /// How many times in a row AoC has rejected a registered leaderboard's credentials while
+    /// prefetching each of its boards, keyed by guild, leaderboard name and board. Registering a
+    /// leaderboard again clears its counts.
+    fn prefetch_rejections(&self) -> StorageResult<HashMap<(GuildId, String, String), u32>>;
+    /// Count a rejection of a leaderboard's credentials for a board, returning how many in a row
+    fn add_prefetch_rejection(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        leaderboard_id: &str,
+    ) -> StorageResult<u32>;
+    /// Forget the rejections of a leaderboard's credentials for a board, after they've worked
+    fn clear_prefetch_rejections(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        leaderboard_id: &str,
+    ) -> StorageResult<()>;
+
+    fn daily_leaderboard_configs(
+        &self,
+    ) -> StorageResult<HashMap<ChannelId, DailyLeaderboardConfig>>;
+    fn set_daily_leaderboard_config(
+        &self,
+        channel_id: ChannelId,
+        config: &DailyLeaderboardConfig,
+    ) -> StorageResult<()>;
+    fn remove_daily_leaderboard_config(&self, channel_id: ChannelId) -> StorageResult<bool>;
+    /// Remember when the daily leaderboard that was just sent was due, clearing any failures
+    fn set_daily_leaderboard_posted(
+        &self,
+        channel_id: ChannelId,
+        due: DateTime<Utc>,
+    ) -> StorageResult<()>;
+    /// Count a failed post of a daily leaderboard, returning how many have failed in a row
+    fn add_daily_leaderboard_failure(&self, channel_id: ChannelId) -> StorageResult<u32>;
+
+    fn daily_puzzle_configs(&self) -> StorageResult<HashMap<ChannelId, DailyPuzzleConfig>>;
+    fn set_daily_puzzle_config(
+        &self,
+        channel_id: ChannelId,
+        config: &DailyPuzzleConfig,
+    ) -> StorageResult<()>;
+    fn remove_daily_puzzle_config(&self, channel_id: ChannelId) -> StorageResult<bool>;
+    /// Remember when the daily puzzle that was just sent was due, clearing any failures
+    fn set_daily_puzzle_posted(
+        &self,
+        channel_id: ChannelId,
+        due: DateTime<Utc>,
+    ) -> StorageResult<()>;
+    /// Count a failed post of a daily puzzle, returning how many have failed in a row
+    fn add_daily_puzzle_failure(&self, channel_id: ChannelId) -> StorageResult<u32>;
+
+    fn star_notify_configs(&self) -> StorageResult<HashMap<ChannelId, StarNotifyConfig>>;
+    fn set_star_notify_config(
+        &self,
+        channel_id: ChannelId,
+        config: &StarNotifyConfig,
+    ) -> StorageResult<()>;
+    fn remove_star_notify_config(&self, channel_id: ChannelId) -> StorageResult<bool>;
+
+    fn role_reward_configs(&self) -> StorageResult<HashMap<RoleId, RoleRewardConfig>>;
+    fn set_role_reward_config(
+        &self,
+        role_id: RoleId,
+        config: &RoleRewardConfig,
+    ) -> StorageResult<()>;
+    fn remove_role_reward_config(&self, role_id: RoleId) -> StorageResult<bool>;
+
+    /// Discord users linked to AoC members in a guild, keyed by AoC member ID
+    fn member_links(&self, guild_id: GuildId) -> StorageResult<HashMap<usize, UserId>>;
+    /// Link an AoC member to a user, replacing any other link either of them had
+    fn link_member(
+        &self,
+        guild_id: GuildId,
+        member_id: usize,
+        user_id: UserId,
+    ) -> StorageResult<()>;
+    /// Remove the link for an AoC member, returning the user they were linked to
+    fn unlink_member(&self, guild_id: GuildId, member_id: usize) -> StorageResult<Option<UserId>>;
+    /// Remove the link for a user, returning whether they had one
+    fn unlink_user(&self, guild_id: GuildId, user_id: UserId) -> StorageResult<bool>;
+
+    /// The timezone a guild picked, if it has
+    fn guild_timezone(&self, guild_id: GuildId) -> StorageResult<Option<Tz>>;
+    /// Pick the timezone for a guild, or go back to the default
+    fn set_guild_timezone(&self, guild_id: GuildId, timezone: Option<Tz>) -> StorageResult<()>;
+
+    /// The timezone a guild's dailies are scheduled in and its times are shown in
+    fn guild_timezone_or_default(&self, guild_id: GuildId) -> StorageResult<Tz> {
+        Ok(self.guild_timezone(guild_id)?.unwrap_or(DEFAULT_TIMEZONE))
+    }
+}
+
+fn database_path() -> String {
+    env::var("DATABASE_PATH").unwrap_or_else(|_| DEFAULT_DATABASE_FILE.to_owned())
+}
+
+/// Get a token cipher from a key in the environment
+pub fn cipher_from_env(var: &str) -> TokenCipher {
+    let key = env::var(var).unwrap_or_else(|_| {
+        panic!(
+            "Expected {var} in environment, generate one with `advent-of-code-friend generate-key`"
+        )
+    });
+    TokenCipher::from_key(&key).unwrap_or_else(|err| panic!("Invalid {var}: {err}"))
+}
+
+/// Open the database at `DATABASE_PATH` (or `aoc-friend.db`), importing `config.json` if it's there.
+/// Tokens are encrypted with the key in `TOKEN_KEY`.
+pub fn open_from_env() -> StorageResult<SharedStorage> {
+    let path = database_path();
+    let storage = SqliteStorage::open(&path, cipher_from_env("TOKEN_KEY"))?;
+    match import_config_file(&storage) {
+        Ok(true) => log::info!("Imported config.json into {path}"),
+        Ok(false) => {}
+        Err(err) => log::error!("Failed to import config.json: {err}"),
+    }
+
+    // Tokens from before encryption was added, or just imported, are still in plaintext
+    let encrypted = storage.encrypt_stored_tokens()?;
+    if encrypted > 0 {
+        log::info!("Encrypted {encrypted} stored tokens");
+    }
+
+    Ok(Arc::new(storage))
+}
+
+/// Re-encrypt every stored token from the key in `TOKEN_KEY` to the key in `NEW_TOKEN_KEY`
+pub fn rekey_from_env() -> StorageResult<usize> {
+    let storage = SqliteStorage::open(database_path(), cipher_from_env("TOKEN_KEY"))?;
+    storage.rekey(&cipher_from_env("NEW_TOKEN_KEY"))
+}
@@ -0,0 +1,971 @@
+use std::{collections::HashMap, path::Path, str::FromStr, sync::Mutex};
+
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serenity::model::prelude::{ChannelId, GuildId, RoleId, UserId};
+
+use crate::{
+    aoc::LeaderboardAuth,
+    config::{
+        DailyLeaderboardConfig, DailyPuzzleConfig, GuildConfig, RoleRewardConfig, StarNotifyConfig,
+    },
+};
+
+use super::{cipher::TokenCipher, migrations::migrate, Storage, StorageError, StorageResult};
+
+/// Storage in a single SQLite database file.
+/// Session tokens and view keys are encrypted, and only decrypted when they're read back.
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+    cipher: TokenCipher,
+}
+
+impl SqliteStorage {
+    /// Open a database file, migrating it to the latest schema
+    pub fn open(path: impl AsRef<Path>, cipher: TokenCipher) -> StorageResult<Self> {
+        let mut connection = Connection::open(&path)?;
+        migrate(&mut connection, Some(path.as_ref()))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            cipher,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> StorageResult<Self> {
+        let mut connection = Connection::open_in_memory()?;
+        migrate(&mut connection, None)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            cipher: TokenCipher::from_key(&TokenCipher::generate_key()).unwrap(),
+        })
+    }
+
+    /// Encrypt any credentials stored before encryption was added, returning how many there were
+    pub fn encrypt_stored_tokens(&self) -> StorageResult<usize> {
+        self.reencrypt_tokens(&self.cipher, |stored| !TokenCipher::is_encrypted(stored))
+    }
+
+    /// Re-encrypt every stored credential with a new key, returning how many there were.
+    /// The storage should be reopened with the new key afterwards.
+    pub fn rekey(&self, new_cipher: &TokenCipher) -> StorageResult<usize> {
+        self.reencrypt_tokens(new_cipher, |_| true)
+    }
+
+    fn reencrypt_tokens(
+        &self,
+        new_cipher: &TokenCipher,
+        should_reencrypt: impl Fn(&str) -> bool,
+    ) -> StorageResult<usize> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let stored: Vec<(i64, String)> = transaction
+            .prepare("SELECT rowid, auth_value FROM guild_leaderboards")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        // Decrypt everything before writing anything, so a wrong key changes nothing
+        let mut reencrypted = Vec::new();
+        for (row_id, auth_value) in stored {
+            if should_reencrypt(&auth_value) {
+                let token = self.cipher.decrypt(&auth_value)?;
+                reencrypted.push((row_id, new_cipher.encrypt(&token)));
+            }
+        }
+        for (row_id, auth_value) in &reencrypted {
+            transaction.execute(
+                "UPDATE guild_leaderboards SET auth_value = ? WHERE rowid = ?",
+                params![auth_value, row_id],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(reencrypted.len())
+    }
+
+    /// Run several storage calls as one transaction, so either all of them are saved or none are.
+    /// The connection isn't held between calls, so nothing else should use the storage until
+    /// this returns.
+    pub fn in_transaction<T>(
+        &self,
+        action: impl FnOnce(&Self) -> StorageResult<T>,
+    ) -> StorageResult<T> {
+        self.connection().execute_batch("BEGIN IMMEDIATE")?;
+        let result = action(self).and_then(|value| {
+            self.connection().execute_batch("COMMIT")?;
+            Ok(value)
+        });
+        if result.is_err() {
+            // Nothing to roll back if the transaction already ended
+            self.connection().execute_batch("ROLLBACK").ok();
+        }
+        result
+    }
+
+    /// Whether nothing has been stored yet
+    pub fn is_empty(&self) -> StorageResult<bool> {
+        let connection = self.connection();
+        let tables = [
+            "guild_leaderboards",
+            "daily_leaderboard_configs",
+            "daily_puzzle_configs",
+            "star_notify_configs",
+            "role_reward_configs",
+            "member_links",
+            "guild_settings",
+        ];
+        for table in tables {
+            let count: i64 =
+                connection.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })?;
+            if count > 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic while holding the lock can't leave a statement half applied
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run a query returning `(id, value)` rows and collect them into a map
+    fn query_map<K, V>(
+        &self,
+        sql: &str,
+        read: impl Fn(&Row) -> StorageResult<(K, V)>,
+    ) -> StorageResult<HashMap<K, V>>
+    where
+        K: Eq + std::hash::Hash,
+    {
+        let connection = self.connection();
+        let mut statement = connection.prepare(sql)?;
+        let mut rows = statement.query([])?;
+        let mut map = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let (key, value) = read(row)?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+
+    /// Count a failed post of a daily in a table, returning how many have failed in a row
+    fn add_daily_failure(&self, table: &str, channel_id: ChannelId) -> StorageResult<u32> {
+        let connection = self.connection();
+        connection.execute(
+            &format!("UPDATE {table} SET failures = failures + 1 WHERE channel_id = ?"),
+            [channel_id.0 as i64],
+        )?;
+        Ok(connection
+            .query_row(
+                &format!("SELECT failures FROM {table} WHERE channel_id = ?"),
+                [channel_id.0 as i64],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or(0))
+    }
+
+    fn delete(&self, sql: &str, id: u64) -> StorageResult<bool> {
+        Ok(self.connection().execute(sql, [id as i64])? > 0)
+    }
+}
+
+fn read_id(row: &Row, column: &str) -> StorageResult<u64> {
+    Ok(row.get::<_, i64>(column)? as u64)
+}
+
+fn read_time(row: &Row, column: &str) -> StorageResult<Option<DateTime<Utc>>> {
+    Ok(row
+        .get::<_, Option<i64>>(column)?
+        .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()))
+}
+
+fn read_guild_config(row: &Row, cipher: &TokenCipher) -> StorageResult<GuildConfig> {
+    let auth_value = cipher.decrypt(&row.get::<_, String>("auth_value")?)?;
+    let auth = match row.get::<_, String>("auth_kind")?.as_str() {
+        "session_token" => LeaderboardAuth::SessionToken(auth_value),
+        "view_key" => LeaderboardAuth::ViewKey(auth_value),
+        kind => return Err(StorageError::Corrupt(format!("unknown auth kind {kind}"))),
+    };
+    Ok(GuildConfig {
+        name: row.get("name")?,
+        is_default: row.get("is_default")?,
+        auth,
+        leaderboard_id: row.get("leaderboard_id")?,
+    })
+}
+
+/// Make the first leaderboard of a guild its default, if it doesn't have one
+fn ensure_default(transaction: &Connection, guild_id: GuildId) -> StorageResult<()> {
+    transaction.execute(
+        "UPDATE guild_leaderboards SET is_default = 1
+        WHERE guild_id = ?1
+            AND NOT EXISTS (SELECT 1 FROM guild_leaderboards WHERE guild_id = ?1 AND is_default)
+            AND name = (SELECT MIN(name) FROM guild_leaderboards WHERE guild_id = ?1)",
+        [guild_id.0 as i64],
+    )?;
+    Ok(())
+}
+
+fn parse<T: FromStr>(value: String) -> StorageResult<T> {
+    value
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("couldn't parse {value}")))
+}
+
+impl Storage for SqliteStorage {
+    fn guild_config(
+        &self,
+        guild_id: GuildId,
+        name: Option<&str>,
+    ) -> StorageResult<Option<GuildConfig>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT * FROM guild_leaderboards
+            WHERE guild_id = ?1 AND (name = ?2 OR (?2 IS NULL AND is_default))",
+        )?;
+        let mut rows = statement.query(params![guild_id.0 as i64, name])?;
+        rows.next()?
+            .map(|row| read_guild_config(row, &self.cipher))
+            .transpose()
+    }
+
+    fn guild_configs(&self, guild_id: GuildId) -> StorageResult<Vec<GuildConfig>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT * FROM guild_leaderboards WHERE guild_id = ? ORDER BY is_default DESC, name",
+        )?;
+        let mut rows = statement.query([guild_id.0 as i64])?;
+        let mut configs = Vec::new();
+        while let Some(row) = rows.next()? {
+            configs.push(read_guild_config(row, &self.cipher)?);
+        }
+        Ok(configs)
+    }
+
+    fn all_guild_configs(&self) -> StorageResult<Vec<(GuildId, GuildConfig)>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare("SELECT * FROM guild_leaderboards")?;
+        let mut rows = statement.query([])?;
+        let mut configs = Vec::new();
+        while let Some(row) = rows.next()? {
+            configs.push((
+                GuildId(read_id(row, "guild_id")?),
+                read_guild_config(row, &self.cipher)?,
+            ));
+        }
+        Ok(configs)
+    }
+
+    fn set_guild_config(&self, guild_id: GuildId, config: &GuildConfig) -> StorageResult<()> {
+        let (auth_kind, auth_value) = match &config.auth {
+            LeaderboardAuth::SessionToken(token) => ("session_token", token),
+            LeaderboardAuth::ViewKey(key) => ("view_key", key),
+        };
+        let auth_value = self.cipher.encrypt(auth_value);
+
+        // A savepoint, so this can also be part of a larger transaction
+        let mut connection = self.connection();
+        let transaction = connection.savepoint()?;
+        if config.is_default {
+            transaction.execute(
+                "UPDATE guild_leaderboards SET is_default = 0 WHERE guild_id = ?",
+                [guild_id.0 as i64],
+            )?;
+        }
+        transaction.execute(
+            "INSERT OR REPLACE INTO guild_leaderboards
+                (guild_id, name, is_default, leaderboard_id, auth_kind, auth_value)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                guild_id.0 as i64,
+                config.name,
+                config.is_default,
+                config.leaderboard_id,
+                auth_kind,
+                auth_value
+            ],
+        )?;
+        ensure_default(&transaction, guild_id)?;
+
+        // New credentials, or the same ones registered again, deserve another chance
+        transaction.execute(
+            "DELETE FROM prefetch_rejections WHERE guild_id = ? AND name = ?",
+            params![guild_id.0 as i64, config.name],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove_guild_config(&self, guild_id: GuildId, name: &str) -> StorageResult<bool> {
+        let mut connection = self.connection();
+        let transaction = connection.savepoint()?;
+        let removed = transaction.execute(
+            "DELETE FROM guild_leaderboards WHERE guild_id = ? AND name = ?",
+            params![guild_id.0 as i64, name],
+        )? > 0;
+        ensure_default(&transaction, guild_id)?;
+        transaction.execute(
+            "DELETE FROM prefetch_rejections WHERE guild_id = ? AND name = ?",
+            params![guild_id.0 as i64, name],
+        )?;
+        transaction.commit()?;
+        Ok(removed)
+    }
+
+    fn prefetch_rejections(&self) -> StorageResult<HashMap<(GuildId, String, String), u32>> {
+        self.query_map("SELECT * FROM prefetch_rejections", |row| {
+            Ok((
+                (
+                    GuildId(read_id(row, "guild_id")?),
+                    row.get("name")?,
+                    row.get("leaderboard_id")?,
+                ),
+                row.get("rejections")?,
+            ))
+        })
+    }
+
+    fn add_prefetch_rejection(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        leaderboard_id: &str,
+    ) -> StorageResult<u32> {
+        Ok(self.connection().query_row(
+            "INSERT INTO prefetch_rejections (guild_id, name, leaderboard_id, rejections)
+                VALUES (?, ?, ?, 1)
+            ON CONFLICT DO UPDATE SET rejections = rejections + 1
+            RETURNING rejections",
+            params![guild_id.0 as i64, name, leaderboard_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn clear_prefetch_rejections(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        leaderboard_id: &str,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "DELETE FROM prefetch_rejections WHERE guild_id = ? AND name = ? AND leaderboard_id = ?",
+            params![guild_id.0 as i64, name, leaderboard_id],
+        )?;
+        Ok(())
+    }
+
+    fn daily_leaderboard_configs(
+        &self,
+    ) -> StorageResult<HashMap<ChannelId, DailyLeaderboardConfig>> {
+        self.query_map("SELECT * FROM daily_leaderboard_configs", |row| {
+            Ok((
+                ChannelId(read_id(row, "channel_id")?),
+                DailyLeaderboardConfig {
+                    guild_id: GuildId(read_id(row, "guild_id")?),
+                    hour: row.get("hour")?,
+                    ordering: parse(row.get("ordering")?)?,
+                    board: row.get("board")?,
+                    schedule: row
+                        .get::<_, Option<String>>("schedule")?
+                        .map(parse)
+                        .transpose()?,
+                    last_posted: read_time(row, "last_posted")?,
+                    failures: row.get("failures")?,
+                },
+            ))
+        })
+    }
+
+    fn set_daily_leaderboard_config(
+        &self,
+        channel_id: ChannelId,
+        config: &DailyLeaderboardConfig,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO daily_leaderboard_configs
+                (channel_id, guild_id, hour, ordering, board, schedule, last_posted, failures)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                channel_id.0 as i64,
+                config.guild_id.0 as i64,
+                config.hour,
+                config.ordering.to_string(),
+                config.board,
+                config
+                    .schedule
+                    .as_ref()
+                    .map(|schedule| schedule.to_string()),
+                config.last_posted.map(|time| time.timestamp()),
+                config.failures
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn remove_daily_leaderboard_config(&self, channel_id: ChannelId) -> StorageResult<bool> {
+        self.delete(
+            "DELETE FROM daily_leaderboard_configs WHERE channel_id = ?",
+            channel_id.0,
+        )
+    }
+
+    fn set_daily_leaderboard_posted(
+        &self,
+        channel_id: ChannelId,
+        due: DateTime<Utc>,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "UPDATE daily_leaderboard_configs SET last_posted = ?, failures = 0 WHERE channel_id = ?",
+            params![due.timestamp(), channel_id.0 as i64],
+        )?;
+        Ok(())
+    }
+
+    fn add_daily_leaderboard_failure(&self, channel_id: ChannelId) -> StorageResult<u32> {
+        self.add_daily_failure("daily_leaderboard_configs", channel_id)
+    }
+
+    fn daily_puzzle_configs(&self) -> StorageResult<HashMap<ChannelId, DailyPuzzleConfig>> {
+        self.query_map("SELECT * FROM daily_puzzle_configs", |row| {
+            Ok((
+                ChannelId(read_id(row, "channel_id")?),
+                DailyPuzzleConfig {
+                    guild_id: GuildId(read_id(row, "guild_id")?),
+                    hour: row.get("hour")?,
+                    schedule: row
+                        .get::<_, Option<String>>("schedule")?
+                        .map(parse)
+                        .transpose()?,
+                    last_posted: read_time(row, "last_posted")?,
+                    failures: row.get("failures")?,
+                },
+            ))
+        })
+    }
+
+    fn set_daily_puzzle_config(
+        &self,
+        channel_id: ChannelId,
+        config: &DailyPuzzleConfig,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO daily_puzzle_configs
+                (channel_id, guild_id, hour, schedule, last_posted, failures)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                channel_id.0 as i64,
+                config.guild_id.0 as i64,
+                config.hour,
+                config
+                    .schedule
+                    .as_ref()
+                    .map(|schedule| schedule.to_string()),
+                config.last_posted.map(|time| time.timestamp()),
+                config.failures
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn remove_daily_puzzle_config(&self, channel_id: ChannelId) -> StorageResult<bool> {
+        self.delete(
+            "DELETE FROM daily_puzzle_configs WHERE channel_id = ?",
+            channel_id.0,
+        )
+    }
+
+    fn set_daily_puzzle_posted(
+        &self,
+        channel_id: ChannelId,
+        due: DateTime<Utc>,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "UPDATE daily_puzzle_configs SET last_posted = ?, failures = 0 WHERE channel_id = ?",
+            params![due.timestamp(), channel_id.0 as i64],
+        )?;
+        Ok(())
+    }
+
+    fn add_daily_puzzle_failure(&self, channel_id: ChannelId) -> StorageResult<u32> {
+        self.add_daily_failure("daily_puzzle_configs", channel_id)
+    }
+
+    fn star_notify_configs(&self) -> StorageResult<HashMap<ChannelId, StarNotifyConfig>> {
+        self.query_map("SELECT * FROM star_notify_configs", |row| {
+            Ok((
+                ChannelId(read_id(row, "channel_id")?),
+                StarNotifyConfig {
+                    guild_id: GuildId(read_id(row, "guild_id")?),
+                    board: row.get("board")?,
+                },
+            ))
+        })
+    }
+
+    fn set_star_notify_config(
+        &self,
+        channel_id: ChannelId,
+        config: &StarNotifyConfig,
+    ) -> StorageResult<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO star_notify_configs (channel_id, guild_id, board)
+                VALUES (?, ?, ?)",
+            params![channel_id.0 as i64, config.guild_id.0 as i64, config.board],
+        )?;
+        Ok(())
+    }
+
+    fn remove_star_notify_config(&self, channel_id: ChannelId) -> StorageResult<bool> {
+        self.delete(
+            "DELETE FROM star_notify_configs WHERE channel_id = ?",
+            channel_id.0,
+        )
+    }
+
+    fn role_reward_configs(&self) -> StorageResult<HashMap<RoleId, RoleRewardConfig>> {
+        self.query_map("SELECT * FROM role_reward_configs", |row| {
+            let requirement: String = row.get("requirement")?;
+            Ok((
+                RoleId(read_id(row, "role_id")?),
+                RoleRewardConfig {
+                    guild_id: GuildId(read_id(row, "guild_id")?),
+                    requirement: serde_json::from_str(&requirement)
+                        .map_err(|err| StorageError::Corrupt(err.to_string()))?,
+                },
+            ))
+        })
+    }
+
+    fn set_role_reward_config(
+        &self,
+        role_id: RoleId,
+        config: &RoleRewardConfig,
+    ) -> StorageResult<()> {
+        let requirement = serde_json::to_string(&config.requirement)
+            .map_err(|err| StorageError::Corrupt(err.to_string()))?;
+        self.connection().execute(
+            "INSERT OR REPLACE INTO role_reward_configs (role_id, guild_id, requirement)
+            VALUES (?, ?, ?)",
+            params![role_id.0 as i64, config.guild_id.0 as i64, requirement],
+        )?;
+        Ok(())
+    }
+
+    fn remove_role_reward_config(&self, role_id: RoleId) -> StorageResult<bool> {
+        self.delete(
+            "DELETE FROM role_reward_configs WHERE role_id = ?",
+            role_id.0,
+        )
+    }
+
+    fn member_links(&self, guild_id: GuildId) -> StorageResult<HashMap<usize, UserId>> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT member_id, user_id FROM member_links WHERE guild_id = ?")?;
+        let mut rows = statement.query([guild_id.0 as i64])?;
+        let mut links = HashMap::new();
+        while let Some(row) = rows.next()? {
+            links.insert(
+                row.get::<_, i64>("member_id")? as usize,
+                UserId(read_id(row, "user_id")?),
+            );
+        }
+        Ok(links)
+    }
+
+    fn link_member(
+        &self,
+        guild_id: GuildId,
+        member_id: usize,
+        user_id: UserId,
+    ) -> StorageResult<()> {
+        // Replacing on either unique key removes the old link for the member and the user
+        self.connection().execute(
+            "INSERT OR REPLACE INTO member_links (guild_id, member_id, user_id) VALUES (?, ?, ?)",
+            params![guild_id.0 as i64, member_id as i64, user_id.0 as i64],
+        )?;
+        Ok(())
+    }
+
+    fn unlink_member(&self, guild_id: GuildId, member_id: usize) -> StorageResult<Option<UserId>> {
+        let user_id: Option<i64> = self
+            .connection()
+            .query_row(
+                "DELETE FROM member_links WHERE guild_id = ? AND member_id = ? RETURNING user_id",
+                params![guild_id.0 as i64, member_id as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(user_id.map(|user_id| UserId(user_id as u64)))
+    }
+
+    fn unlink_user(&self, guild_id: GuildId, user_id: UserId) -> StorageResult<bool> {
+        Ok(self.connection().execute(
+            "DELETE FROM member_links WHERE guild_id = ? AND user_id = ?",
+            params![guild_id.0 as i64, user_id.0 as i64],
+        )? > 0)
+    }
+
+    fn guild_timezone(&self, guild_id: GuildId) -> StorageResult<Option<Tz>> {
+        let timezone: Option<String> = self
+            .connection()
+            .query_row(
+                "SELECT timezone FROM guild_settings WHERE guild_id = ?",
+                [guild_id.0 as i64],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        timezone.map(parse).transpose()
+    }
+
+    fn set_guild_timezone(&self, guild_id: GuildId, timezone: Option<Tz>) -> StorageResult<()> {
+        self.connection().execute(
+            "INSERT INTO guild_settings (guild_id, timezone) VALUES (?, ?)
+            ON CONFLICT (guild_id) DO UPDATE SET timezone = excluded.timezone",
+            params![guild_id.0 as i64, timezone.map(|timezone| timezone.name())],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+    use super::SqliteStorage;
+    use crate::{
+        aoc::LeaderboardAuth,
+        config::{
+            DailyLeaderboardConfig, DailyPuzzleConfig, GuildConfig, LeaderboardOrdering,
+            StarNotifyConfig, MAX_DAILY_FAILURES,
+        },
+        storage::{Storage, StorageError, StorageResult, TokenCipher},
+    };
+
+    fn guild_config(name: &str, is_default: bool, leaderboard_id: &str) -> GuildConfig {
+        GuildConfig {
+            name: name.to_owned(),
+            is_default,
+            auth: LeaderboardAuth::ViewKey("key".to_owned()),
+            leaderboard_id: leaderboard_id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_guild_config_round_trip() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        storage
+            .set_guild_config(GuildId(1), &guild_config("work", false, "1234"))
+            .unwrap();
+
+        // A guild's only leaderboard is its default
+        let stored = storage.guild_config(GuildId(1), None).unwrap().unwrap();
+        assert_eq!(stored.name, "work");
+        assert!(stored.is_default);
+        assert_eq!(stored.leaderboard_id, "1234");
+        assert!(matches!(stored.auth, LeaderboardAuth::ViewKey(ref key) if key == "key"));
+        assert!(storage.guild_config(GuildId(2), None).unwrap().is_none());
+
+        // Only the encrypted key is stored
+        let stored: String = storage
+            .connection()
+            .query_row("SELECT auth_value FROM guild_leaderboards", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_ne!(stored, "key");
+
+        assert!(storage.remove_guild_config(GuildId(1), "work").unwrap());
+        assert!(!storage.remove_guild_config(GuildId(1), "work").unwrap());
+        assert!(storage.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_failed_transaction_is_rolled_back() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let result: StorageResult<()> = storage.in_transaction(|storage| {
+            storage.set_guild_config(GuildId(1), &guild_config("work", true, "1234"))?;
+            storage.link_member(GuildId(1), 101, UserId(5))?;
+            Err(StorageError::Corrupt("something went wrong".to_owned()))
+        });
+        assert!(result.is_err());
+        assert!(storage.is_empty().unwrap());
+
+        // And the storage can still be used afterwards
+        storage
+            .in_transaction(|storage| storage.link_member(GuildId(1), 101, UserId(5)))
+            .unwrap();
+        assert!(!storage.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_named_leaderboards() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let guild_id = GuildId(1);
+        storage
+            .set_guild_config(guild_id, &guild_config("work", false, "1234"))
+            .unwrap();
+        storage
+            .set_guild_config(guild_id, &guild_config("uni", false, "5678"))
+            .unwrap();
+
+        let get = |name| storage.guild_config(guild_id, name).unwrap().unwrap();
+        assert_eq!(get(None).name, "work");
+        assert_eq!(get(Some("uni")).leaderboard_id, "5678");
+        assert!(storage
+            .guild_config(guild_id, Some("club"))
+            .unwrap()
+            .is_none());
+
+        // Only one leaderboard can be the default
+        storage
+            .set_guild_config(guild_id, &guild_config("uni", true, "5678"))
+            .unwrap();
+        assert_eq!(get(None).name, "uni");
+        let names: Vec<_> = storage
+            .guild_configs(guild_id)
+            .unwrap()
+            .into_iter()
+            .map(|config| (config.name, config.is_default))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("uni".to_owned(), true), ("work".to_owned(), false)]
+        );
+
+        // Removing the default makes another leaderboard the default
+        storage.remove_guild_config(guild_id, "uni").unwrap();
+        assert_eq!(get(None).name, "work");
+        assert_eq!(storage.all_guild_configs().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rekey() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        storage
+            .set_guild_config(GuildId(1), &guild_config("work", true, "1234"))
+            .unwrap();
+
+        // A token saved before encryption existed
+        storage
+            .connection()
+            .execute(
+                "INSERT INTO guild_leaderboards VALUES (2, 'default', 1, '5678', 'session_token', 'plain')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(storage.encrypt_stored_tokens().unwrap(), 1);
+        assert_eq!(storage.encrypt_stored_tokens().unwrap(), 0);
+
+        let new_cipher = TokenCipher::from_key(&TokenCipher::generate_key()).unwrap();
+        assert_eq!(storage.rekey(&new_cipher).unwrap(), 2);
+        assert!(matches!(
+            storage.guild_config(GuildId(1), None),
+            Err(StorageError::WrongKey)
+        ));
+
+        storage.cipher = new_cipher;
+        let stored = storage.guild_config(GuildId(2), None).unwrap().unwrap();
+        assert!(matches!(stored.auth, LeaderboardAuth::SessionToken(ref t) if t == "plain"));
+    }
+
+    #[test]
+    fn test_star_notify_board_is_stored() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        for (channel_id, board) in [(10, None), (11, Some("uni".to_owned()))] {
+            storage
+                .set_star_notify_config(
+                    ChannelId(channel_id),
+                    &StarNotifyConfig {
+                        guild_id: GuildId(1),
+                        board,
+                    },
+                )
+                .unwrap();
+        }
+
+        let stored = storage.star_notify_configs().unwrap();
+        assert_eq!(stored[&ChannelId(10)].board, None);
+        assert_eq!(stored[&ChannelId(11)].board.as_deref(), Some("uni"));
+    }
+
+    #[test]
+    fn test_daily_leaderboard_ordering_is_stored() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let config = DailyLeaderboardConfig {
+            guild_id: GuildId(1),
+            hour: 9,
+            ordering: LeaderboardOrdering::MedianSolveTime,
+            board: Some("uni".to_owned()),
+            schedule: Some("0 9,17 * 12 MON-FRI".parse().unwrap()),
+            last_posted: None,
+            failures: 0,
+        };
+        storage
+            .set_daily_leaderboard_config(ChannelId(10), &config)
+            .unwrap();
+
+        let stored = storage.daily_leaderboard_configs().unwrap();
+        assert_eq!(stored[&ChannelId(10)].hour, 9);
+        assert_eq!(
+            stored[&ChannelId(10)].ordering,
+            LeaderboardOrdering::MedianSolveTime
+        );
+        assert_eq!(stored[&ChannelId(10)].board.as_deref(), Some("uni"));
+        assert_eq!(
+            stored[&ChannelId(10)].posting_schedule().to_string(),
+            "0 9,17 * 12 MON-FRI"
+        );
+        assert_eq!(stored[&ChannelId(10)].last_posted, None);
+
+        // Posting is recorded without changing the rest of the config
+        let due = Utc.with_ymd_and_hms(2023, 12, 1, 14, 0, 0).unwrap();
+        storage
+            .set_daily_leaderboard_posted(ChannelId(10), due)
+            .unwrap();
+        let stored = storage.daily_leaderboard_configs().unwrap();
+        assert_eq!(stored[&ChannelId(10)].last_posted, Some(due));
+        assert_eq!(stored[&ChannelId(10)].board.as_deref(), Some("uni"));
+    }
+
+    #[test]
+    fn test_daily_failures() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let config = DailyPuzzleConfig {
+            guild_id: GuildId(1),
+            hour: 0,
+            schedule: None,
+            last_posted: None,
+            failures: 0,
+        };
+        storage
+            .set_daily_puzzle_config(ChannelId(10), &config)
+            .unwrap();
+
+        // Failures add up until a post succeeds
+        assert_eq!(storage.add_daily_puzzle_failure(ChannelId(10)).unwrap(), 1);
+        assert_eq!(storage.add_daily_puzzle_failure(ChannelId(10)).unwrap(), 2);
+        assert_eq!(
+            storage.daily_puzzle_configs().unwrap()[&ChannelId(10)].failures,
+            2
+        );
+        let due = Utc.with_ymd_and_hms(2023, 12, 1, 5, 0, 0).unwrap();
+        storage.set_daily_puzzle_posted(ChannelId(10), due).unwrap();
+        assert_eq!(
+            storage.daily_puzzle_configs().unwrap()[&ChannelId(10)].failures,
+            0
+        );
+
+        // Setting the daily up again turns it back on
+        for _ in 0..MAX_DAILY_FAILURES {
+            storage.add_daily_puzzle_failure(ChannelId(10)).unwrap();
+        }
+        assert!(storage.daily_puzzle_configs().unwrap()[&ChannelId(10)].is_disabled());
+        storage
+            .set_daily_puzzle_config(ChannelId(10), &config)
+            .unwrap();
+        assert!(!storage.daily_puzzle_configs().unwrap()[&ChannelId(10)].is_disabled());
+    }
+
+    #[test]
+    fn test_prefetch_rejections() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let config = GuildConfig {
+            name: "default".to_owned(),
+            is_default: true,
+            auth: LeaderboardAuth::ViewKey("key".to_owned()),
+            leaderboard_id: "1,2".to_owned(),
+        };
+        storage.set_guild_config(GuildId(1), &config).unwrap();
+        let rejections = |leaderboard_id: &str| {
+            storage
+                .prefetch_rejections()
+                .unwrap()
+                .get(&(GuildId(1), "default".to_owned(), leaderboard_id.to_owned()))
+                .copied()
+        };
+
+        // Rejections add up for each board until the credentials work
+        assert_eq!(
+            storage
+                .add_prefetch_rejection(GuildId(1), "default", "1")
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            storage
+                .add_prefetch_rejection(GuildId(1), "default", "1")
+                .unwrap(),
+            2
+        );
+        storage
+            .add_prefetch_rejection(GuildId(1), "default", "2")
+            .unwrap();
+        storage
+            .clear_prefetch_rejections(GuildId(1), "default", "2")
+            .unwrap();
+        assert_eq!(rejections("1"), Some(2));
+        assert_eq!(rejections("2"), None);
+
+        // Registering again, even with the same credentials, clears them
+        storage.set_guild_config(GuildId(1), &config).unwrap();
+        assert_eq!(rejections("1"), None);
+    }
+
+    #[test]
+    fn test_guild_timezone() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let guild_id = GuildId(1);
+        assert_eq!(storage.guild_timezone(guild_id).unwrap(), None);
+        assert_eq!(
+            storage.guild_timezone_or_default(guild_id).unwrap(),
+            chrono_tz::EST
+        );
+
+        storage
+            .set_guild_timezone(guild_id, Some(chrono_tz::Australia::Sydney))
+            .unwrap();
+        assert_eq!(
+            storage.guild_timezone(guild_id).unwrap(),
+            Some(chrono_tz::Australia::Sydney)
+        );
+
+        storage.set_guild_timezone(guild_id, None).unwrap();
+        assert_eq!(storage.guild_timezone(guild_id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_member_links_are_unique() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let guild_id = GuildId(1);
+        storage.link_member(guild_id, 101, UserId(5)).unwrap();
+
+        // Linking the user to another member moves their link
+        storage.link_member(guild_id, 102, UserId(5)).unwrap();
+        assert_eq!(
+            storage.member_links(guild_id).unwrap(),
+            [(102, UserId(5))].into()
+        );
+
+        // Linking the member to another user replaces it
+        storage.link_member(guild_id, 102, UserId(6)).unwrap();
+        assert_eq!(
+            storage.member_links(guild_id).unwrap(),
+            [(102, UserId(6))].into()
+        );
+
+        assert_eq!(
+            storage.unlink_member(guild_id, 102).unwrap(),
+            Some(UserId(6))
+        );
+        assert!(!storage.unlink_user(guild_id, UserId(6)).unwrap());
+    }
+}